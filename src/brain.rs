@@ -0,0 +1,363 @@
+use super::*;
+
+/// Decides where a snake moves next
+pub trait SnakeBrain {
+    fn kind(&self) -> BrainKind;
+    /// Copy of everything the brain remembers, for saving the game
+    fn state(&self) -> BrainState;
+    /// Direction of the next move of snake `id`, `None` if there is nowhere to go
+    fn think(&mut self, id: Id, map: &Map, rng: &mut StdRng) -> Option<vec2<isize>>;
+}
+
+/// Brain selection in [Config]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrainKind {
+    /// Goes for the closest food or player in vision, chases own tail otherwise
+    Classic,
+    /// Predicts where the player is heading and cuts off their escape routes
+    Hunter,
+}
+
+impl BrainKind {
+    pub fn create(self, config: &Config) -> Box<dyn SnakeBrain> {
+        match self {
+            Self::Classic => Box::new(ClassicBrain::new(config)),
+            Self::Hunter => Box::new(HunterBrain::new(config)),
+        }
+    }
+}
+
+/// Saved state of any brain, see [SnakeBrain::state]
+#[derive(Serialize, Deserialize)]
+pub enum BrainState {
+    Classic(ClassicBrain),
+    Hunter(HunterBrain),
+}
+
+impl BrainState {
+    pub fn into_brain(self) -> Box<dyn SnakeBrain> {
+        match self {
+            Self::Classic(brain) => Box::new(brain),
+            Self::Hunter(brain) => Box::new(brain),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ClassicBrain {
+    vision: usize,
+    target_pos: Option<vec2<usize>>,
+}
+
+impl ClassicBrain {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            vision: config.snake_vision,
+            target_pos: None,
+        }
+    }
+}
+
+impl SnakeBrain for ClassicBrain {
+    fn kind(&self) -> BrainKind {
+        BrainKind::Classic
+    }
+
+    fn state(&self) -> BrainState {
+        BrainState::Classic(self.clone())
+    }
+
+    fn think(&mut self, id: Id, map: &Map, rng: &mut StdRng) -> Option<vec2<isize>> {
+        let head_pos = snake::head(id, map);
+        if let Some(pos) = find_closest_food(id, self.vision, map) {
+            self.target_pos = Some(pos);
+        } else if self.target_pos.is_none() {
+            self.target_pos = Some(vec2(
+                rng.gen_range(0..map.size().x),
+                rng.gen_range(0..map.size().y),
+            ));
+        }
+        if let Some(next) = step_towards(id, map, self.target_pos.unwrap(), rng) {
+            return map.dir_to(head_pos, next);
+        }
+
+        self.target_pos = None;
+        if let Some(next) = step_towards(id, map, snake::tail(id, map), rng) {
+            return map.dir_to(head_pos, next);
+        }
+        map.neighbors(head_pos)
+            .filter(|&pos| !is_obstacle(&map[pos]))
+            .choose(rng)
+            .and_then(|next| map.dir_to(head_pos, next))
+    }
+}
+
+/// How many cells ahead of the player the hunter aims
+const HUNTER_LOOKAHEAD: usize = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HunterBrain {
+    /// Player steps made per snake step
+    player_speed_ratio: f64,
+    last_player_pos: Option<vec2<usize>>,
+    /// Direction the player moved in last
+    player_dir: Option<vec2<isize>>,
+    /// Used when there is no player to hunt
+    fallback: ClassicBrain,
+}
+
+impl HunterBrain {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            player_speed_ratio: config.player_speed / config.snake_speed,
+            last_player_pos: None,
+            player_dir: None,
+            fallback: ClassicBrain::new(config),
+        }
+    }
+
+    fn observe_player(&mut self, map: &Map, player_pos: vec2<usize>) {
+        if let Some(last_pos) = self.last_player_pos {
+            // Diff takes the shortest way around the map edges, portals need the actual step
+            let diff = map.diff(player_pos, last_pos);
+            if let Some(dir) = map.dir_to(last_pos, player_pos) {
+                self.player_dir = Some(dir);
+            } else if diff != vec2::ZERO {
+                self.player_dir = Some(if diff.x.abs() >= diff.y.abs() {
+                    vec2(diff.x.signum(), 0)
+                } else {
+                    vec2(0, diff.y.signum())
+                });
+            }
+        }
+        self.last_player_pos = Some(player_pos);
+    }
+
+    /// Where the player will be if they keep going in the same direction
+    fn predict_player(&self, map: &Map, player_pos: vec2<usize>) -> vec2<usize> {
+        let Some(dir) = self.player_dir else {
+            return player_pos;
+        };
+        let mut pos = player_pos;
+        for _ in 0..HUNTER_LOOKAHEAD {
+            let next = map.add_dir(pos, dir);
+            if is_obstacle(&map[next]) {
+                break;
+            }
+            pos = next;
+        }
+        pos
+    }
+
+    /// Move cutting off the player at `player_pos`, heading to where they are expected to be
+    fn chase(
+        &mut self,
+        id: Id,
+        map: &Map,
+        player_pos: vec2<usize>,
+        predicted_pos: vec2<usize>,
+        rng: &mut StdRng,
+    ) -> Option<vec2<isize>> {
+        let head_pos = snake::head(id, map);
+        let player_d = distances(map, player_pos);
+        let body_len = map.snake_body(id).count();
+
+        // Pick the move leaving the player the fewest cells they can reach before us
+        let mut best_score = None;
+        let mut best_moves = Vec::new();
+        for next in map.neighbors(head_pos) {
+            if !snake::can_enter(id, map, next) {
+                continue;
+            }
+            if next == player_pos {
+                return map.dir_to(head_pos, next);
+            }
+            let snake_d = distances(map, next);
+            if snake_d[player_pos.x][player_pos.y].is_none() {
+                // The player is walled off from this cell
+                continue;
+            }
+            let mut player_cells = 0;
+            for (player_column, snake_column) in player_d.iter().zip(&snake_d) {
+                for (&player_steps, &snake_steps) in player_column.iter().zip(snake_column) {
+                    let Some(player_steps) = player_steps else {
+                        continue;
+                    };
+                    let player_first = snake_steps.is_none_or(|snake_steps| {
+                        player_steps as f64 / self.player_speed_ratio < (snake_steps + 1) as f64
+                    });
+                    if player_first {
+                        player_cells += 1;
+                    }
+                }
+            }
+            // Not getting stuck in a pocket smaller than ourselves comes first
+            let space = snake_d.iter().flatten().filter(|d| d.is_some()).count();
+            let trapped = space < body_len;
+            let chase =
+                snake_d[predicted_pos.x][predicted_pos.y].unwrap_or(map.size().x + map.size().y);
+            let score = (trapped, player_cells, chase);
+            match best_score {
+                Some(best) if best < score => {}
+                Some(best) if best == score => best_moves.push(next),
+                _ => {
+                    best_score = Some(score);
+                    best_moves = vec![next];
+                }
+            }
+        }
+        match best_moves.choose(rng) {
+            Some(&next) => map.dir_to(head_pos, next),
+            None => self.fallback.think(id, map, rng),
+        }
+    }
+}
+
+impl SnakeBrain for HunterBrain {
+    fn kind(&self) -> BrainKind {
+        BrainKind::Hunter
+    }
+
+    fn state(&self) -> BrainState {
+        BrainState::Hunter(self.clone())
+    }
+
+    fn think(&mut self, id: Id, map: &Map, rng: &mut StdRng) -> Option<vec2<isize>> {
+        let head_pos = snake::head(id, map);
+        // Decoys go before real players, they stand still so there is nothing to predict
+        if let Some(decoy_pos) = map
+            .decoy_positions()
+            .min_by_key(|&pos| map.distance(head_pos, pos))
+        {
+            self.last_player_pos = None;
+            self.player_dir = None;
+            return self.chase(id, map, decoy_pos, decoy_pos, rng);
+        }
+        let Some(player_pos) = map
+            .player_positions()
+            .min_by_key(|&pos| map.distance(head_pos, pos))
+        else {
+            self.last_player_pos = None;
+            self.player_dir = None;
+            return self.fallback.think(id, map, rng);
+        };
+        self.observe_player(map, player_pos);
+        let predicted_pos = self.predict_player(map, player_pos);
+        self.chase(id, map, player_pos, predicted_pos, rng)
+    }
+}
+
+/// Whether the BFS helpers go around the cell
+fn is_obstacle(cell: &MapCell) -> bool {
+    matches!(
+        cell,
+        MapCell::Wall | MapCell::SnakePart { .. } | MapCell::Portal(_)
+    )
+}
+
+/// Distances from `from` to every cell reachable around walls and snakes,
+/// portals connect the cells next to them
+pub fn distances(map: &Map, from: vec2<usize>) -> Vec<Vec<Option<usize>>> {
+    let mut d = vec![vec![None::<usize>; map.size().y]; map.size().x];
+    let mut q = std::collections::VecDeque::new();
+    d[from.x][from.y] = Some(0);
+    q.push_back(from);
+    while let Some(pos) = q.pop_front() {
+        let pos_d = d[pos.x][pos.y].unwrap();
+        for new_pos in map.neighbors(pos) {
+            if is_obstacle(&map[new_pos]) {
+                continue;
+            }
+            if d[new_pos.x][new_pos.y].is_none() {
+                d[new_pos.x][new_pos.y] = Some(pos_d + 1);
+                q.push_back(new_pos);
+            }
+        }
+    }
+    d
+}
+
+/// Closest decoy within `vision` steps of the snake's head, looking through portals,
+/// or the closest player or item if no decoy is in sight
+pub fn find_closest_food(id: Id, vision: usize, map: &Map) -> Option<vec2<usize>> {
+    let head_pos = snake::head(id, map);
+    let mut d = vec![vec![None::<usize>; map.size().y]; map.size().x];
+    let mut q = std::collections::VecDeque::new();
+    d[head_pos.x][head_pos.y] = Some(0);
+    q.push_back(head_pos);
+    let any_decoys = map.decoy_positions().next().is_some();
+    let mut closest = None;
+    while let Some(pos) = q.pop_front() {
+        let pos_d = d[pos.x][pos.y].unwrap();
+        if pos_d >= vision {
+            continue;
+        }
+        for new_pos in map.neighbors(pos) {
+            match map[new_pos] {
+                MapCell::Decoy => return Some(new_pos),
+                // Without decoys around the first find is the closest
+                MapCell::Player(_) | MapCell::Item(_) if !any_decoys => return Some(new_pos),
+                MapCell::Player(_) | MapCell::Item(_) => {
+                    closest = closest.or(Some(new_pos));
+                }
+                _ => {}
+            }
+            if d[new_pos.x][new_pos.y].is_none() {
+                d[new_pos.x][new_pos.y] = Some(pos_d + 1);
+                q.push_back(new_pos);
+            }
+        }
+    }
+    closest
+}
+
+/// Next cell on a shortest path from the snake's head to `to`,
+/// picking randomly between paths weighted by how many of them go through each cell
+pub fn step_towards(id: Id, map: &Map, to: vec2<usize>, rng: &mut StdRng) -> Option<vec2<usize>> {
+    let head_pos = snake::head(id, map);
+    let tail_pos = snake::tail(id, map);
+    if to != tail_pos && is_obstacle(&map[to]) {
+        return None;
+    }
+    let mut d = vec![vec![None::<usize>; map.size().y]; map.size().x];
+    let mut nums = vec![vec![0.0; map.size().y]; map.size().x];
+    let mut q = std::collections::VecDeque::new();
+    d[to.x][to.y] = Some(0);
+    nums[to.x][to.y] = 1.0;
+    q.push_back(to);
+    while let Some(pos) = q.pop_front() {
+        let pos_d = d[pos.x][pos.y].unwrap();
+        for new_pos in map.neighbors(pos) {
+            if is_obstacle(&map[new_pos]) {
+                continue;
+            }
+            match d[new_pos.x][new_pos.y] {
+                Some(d) => {
+                    if d == pos_d + 1 {
+                        nums[new_pos.x][new_pos.y] += nums[pos.x][pos.y];
+                    }
+                }
+                None => {
+                    d[new_pos.x][new_pos.y] = Some(pos_d + 1);
+                    nums[new_pos.x][new_pos.y] = nums[pos.x][pos.y];
+                    q.push_back(new_pos);
+                }
+            }
+        }
+    }
+
+    let dist = map
+        .neighbors(head_pos)
+        .filter_map(|next| d[next.x][next.y])
+        .min()?;
+    let choices: Vec<_> = map
+        .neighbors(head_pos)
+        .filter(|next| d[next.x][next.y] == Some(dist))
+        .collect();
+    Some(
+        *choices
+            .choose_weighted(rng, |next| nums[next.x][next.y])
+            .unwrap(),
+    )
+}
@@ -0,0 +1,39 @@
+use super::*;
+
+#[derive(geng::asset::Load, Deserialize)]
+#[load(serde = "toml")]
+pub struct Campaign {
+    /// Map files in `assets/levels`, in the order they are unlocked
+    pub levels: Vec<String>,
+}
+
+pub struct Level {
+    pub file: String,
+    pub map: String,
+}
+
+/// Campaign progress, persisted between runs
+#[derive(Serialize, Deserialize, Default)]
+pub struct Progress {
+    /// Number of levels completed in order
+    pub completed: usize,
+}
+
+impl Progress {
+    const KEY: &'static str = "progress";
+
+    pub fn load() -> Self {
+        preferences::load(Self::KEY).unwrap_or_default()
+    }
+
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level <= self.completed
+    }
+
+    pub fn complete(&mut self, level: usize) {
+        if level >= self.completed {
+            self.completed = level + 1;
+            preferences::save(Self::KEY, self);
+        }
+    }
+}
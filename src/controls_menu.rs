@@ -0,0 +1,126 @@
+use super::*;
+
+use menu::{Menu, MenuInput};
+
+/// Remapping screen, pushed on top of [options::OptionsMenu]
+pub struct ControlsMenu {
+    ctx: Context,
+    controls: Controls,
+    menu: Menu,
+    /// Index of the action waiting for a new key
+    rebinding: Option<usize>,
+    transition: Option<geng::state::Transition>,
+}
+
+impl ControlsMenu {
+    pub fn new(ctx: &Context) -> Self {
+        let mut result = Self {
+            ctx: ctx.clone(),
+            controls: ctx.controls.borrow().clone(),
+            menu: Menu::new("Controls", Vec::new()),
+            rebinding: None,
+            transition: None,
+        };
+        result.update_entries();
+        result
+    }
+
+    fn update_entries(&mut self) {
+        let rebinding = self.rebinding;
+        let mut entries: Vec<String> = self
+            .controls
+            .actions_mut()
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, keys))| {
+                if rebinding == Some(i) {
+                    format!("{name}: press a key...")
+                } else {
+                    let keys: Vec<String> = keys.iter().map(|key| format!("{key:?}")).collect();
+                    format!("{name}: {}", keys.join(", "))
+                }
+            })
+            .collect();
+        entries.push("Reset to defaults".to_owned());
+        entries.push("Back".to_owned());
+        self.menu.entries = entries;
+    }
+
+    /// Apply the controls right away and remember them
+    fn save(&mut self, controls: Option<Controls>) {
+        *self.ctx.controls.borrow_mut() = controls
+            .clone()
+            .unwrap_or_else(|| self.ctx.assets.config.controls.clone());
+        let mut options = options::Options::load(&self.ctx.assets.config);
+        options.controls = controls;
+        options.save();
+        self.controls = self.ctx.controls.borrow().clone();
+        self.update_entries();
+    }
+
+    /// Returns false if the key can not be taken without leaving another action unbound
+    fn bind(&mut self, action: usize, key: geng::Key) -> bool {
+        let mut controls = self.controls.clone();
+        // A key only triggers a single action
+        for (i, (_, keys)) in controls.actions_mut().into_iter().enumerate() {
+            if i == action {
+                *keys = vec![key];
+            } else if *keys == [key] {
+                // Menus are navigated with these, losing one could lock the player out of every menu
+                return false;
+            } else {
+                keys.retain(|&other| other != key);
+            }
+        }
+        self.save(Some(controls));
+        true
+    }
+}
+
+impl geng::State for ControlsMenu {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        if let Some(action) = self.rebinding {
+            if let geng::Event::KeyPress { key } = event {
+                // Escape is reserved for going back, a refused key keeps waiting for another one
+                if key == geng::Key::Escape || self.bind(action, key) {
+                    self.rebinding = None;
+                    self.update_entries();
+                }
+            }
+            return;
+        }
+        let Some(input) = MenuInput::from_event(&self.controls, &event) else {
+            return;
+        };
+        match self.menu.navigate(input) {
+            Some(MenuInput::Back) => self.transition = Some(geng::state::Transition::Pop),
+            Some(MenuInput::Select) => {
+                let actions = self.controls.actions_mut().len();
+                match self.menu.selected {
+                    action if action < actions => {
+                        self.rebinding = Some(action);
+                        self.update_entries();
+                    }
+                    action if action == actions => self.save(None),
+                    _ => self.transition = Some(geng::state::Transition::Pop),
+                }
+            }
+            _ => {}
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let hint = format!(
+            "{} to rebind, Escape to go back",
+            Controls::key_name(&self.controls.use_item)
+        );
+        self.menu.draw(&self.ctx, framebuffer, &hint);
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+
+/// Named preset of [ConfigOverrides], see `difficulties` in `assets/config.toml`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Nightmare,
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 4] = [Self::Easy, Self::Normal, Self::Hard, Self::Nightmare];
+
+    const KEY: &'static str = "difficulty";
+
+    /// Last selected difficulty
+    pub fn load() -> Self {
+        preferences::load(Self::KEY).unwrap_or_default()
+    }
+
+    pub fn save(self) {
+        preferences::save(Self::KEY, &self);
+    }
+
+    /// Apply this preset on top of `config`
+    pub fn apply(self, config: &mut Config) {
+        if let Some(overrides) = config.difficulties.get(&self).cloned() {
+            overrides.apply(config);
+        }
+    }
+
+    /// Neighbouring difficulty, `delta` steps harder
+    pub fn cycle(self, delta: isize) -> Self {
+        let len = Self::ALL.len() as isize;
+        let index = Self::ALL.iter().position(|&d| d == self).unwrap() as isize;
+        Self::ALL[(index + delta).rem_euclid(len) as usize]
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Easy => "easy",
+            Self::Normal => "normal",
+            Self::Hard => "hard",
+            Self::Nightmare => "nightmare",
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use super::*;
+
+/// Shown instead of the game when it fails to start, e.g. because of a broken map
+pub struct ErrorScreen {
+    ctx: Context,
+    error: anyhow::Error,
+    transition: Option<geng::state::Transition>,
+}
+
+impl ErrorScreen {
+    pub fn new(ctx: &Context, error: anyhow::Error) -> Self {
+        log::error!("{error:#}");
+        Self {
+            ctx: ctx.clone(),
+            error,
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for ErrorScreen {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        match event {
+            geng::Event::KeyPress { key }
+                if self.ctx.controls.borrow().fullscreen.contains(&key) =>
+            {
+                self.ctx.geng.window().toggle_fullscreen();
+            }
+            geng::Event::KeyPress {
+                key: geng::Key::Escape,
+            } => {
+                self.transition = Some(geng::state::Transition::Switch(Box::new(
+                    LevelSelect::new(&self.ctx, &[Role::Runner]),
+                )));
+            }
+            _ => {}
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        ugli::clear(framebuffer, Some(Rgba::BLACK), None, None);
+        let config = &self.ctx.assets.config;
+        let camera = geng::Camera2d {
+            center: vec2::ZERO,
+            rotation: Angle::ZERO,
+            fov: config.ui_fov,
+        };
+        self.ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            "Failed to start the game",
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, 2.0)),
+            config.colors.player,
+            config.outline_size,
+            config.outline_color,
+        );
+        for (i, line) in format!("{:#}", self.error).lines().enumerate() {
+            self.ctx.assets.font.draw_with_outline(
+                framebuffer,
+                &camera,
+                line,
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(vec2(0.0, 0.5 - i as f32 * 0.5)) * mat3::scale_uniform(0.3),
+                Rgba::WHITE,
+                config.outline_size,
+                config.outline_color,
+            );
+        }
+    }
+}
@@ -1,15 +1,12 @@
 use super::*;
 
-struct SnakeSpeedModifier {
-    time_left: f64,
-    multiplier: f64,
-}
-
-#[derive(Debug)]
-pub struct Results {
-    time: f64,
-    win: bool,
-    score: usize,
+fn format_time(time: f64) -> String {
+    let millis = (time * 1000.0).round() as i64;
+    let secs = millis / 1000;
+    let mins = secs / 60;
+    let secs = secs % 60;
+    let millis = millis % 1000;
+    format!("{:02}:{:02}.{:03}", mins, secs, millis)
 }
 
 struct Particle {
@@ -20,31 +17,67 @@ struct Particle {
     t: f32,
 }
 
+/// How long messages like "Game saved" stay on screen, in seconds
+const NOTICE_TIME: f64 = 2.0;
+/// Shown on the first Escape in a networked game, a second one while it is up leaves
+const LEAVE_NOTICE: &str = "Press Escape again to leave";
+
+/// Rounds won by each side of a versus match, carried over between rounds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Versus {
+    runner_wins: usize,
+    snake_wins: usize,
+}
+
+impl Versus {
+    fn rounds_played(&self) -> usize {
+        self.runner_wins + self.snake_wins
+    }
+
+    fn is_over(&self, config: &VersusConfig) -> bool {
+        self.rounds_played() >= config.rounds
+            || self.runner_wins.max(self.snake_wins) * 2 > config.rounds
+    }
+
+    fn winner_text(&self) -> &'static str {
+        match self.runner_wins.cmp(&self.snake_wins) {
+            std::cmp::Ordering::Greater => "Runner wins the match!",
+            std::cmp::Ordering::Less => "Snake wins the match!",
+            std::cmp::Ordering::Equal => "The match is a draw!",
+        }
+    }
+}
+
 pub struct Game {
-    id_gen: IdGen,
-    time: f64,
     ctx: Context,
-    map: Map,
-    ai_state: HashMap<Id, snake::AiState>,
+    /// Campaign level being played, `None` for the endless mode
+    level: Option<usize>,
+    difficulty: Difficulty,
+    sim: Simulation,
+    /// Scores of the match when a player steers a snake
+    versus: Option<Versus>,
+    /// Input collected since the last tick, one per player
+    inputs: Vec<simulation::Input>,
+    /// Time accumulated towards the next fixed simulation tick
+    tick_time: f64,
+    replay: Replay,
+    playback: Option<replay::Playback>,
+    /// Connection to the server running this game, which is then only mirrored here
+    remote: Option<net::Client>,
     camera: Camera2d,
-    player_id: Option<Id>,
-    held_item: Option<Item>,
-    next_snake_move: HashMap<Id, f64>,
-    next_player_move: f64,
-    next_item: f64,
-    snake_grow: HashMap<Id, usize>,
-    snake_reversing: HashMap<Id, u32>,
-    snake_speed_modifier: HashMap<Id, SnakeSpeedModifier>,
-    results: Option<Results>,
-    alternate_move: usize,
-    player_moved: bool,
-    score: usize,
     particles: Vec<Particle>,
     music: Option<geng::SoundEffect>,
     show_ui: bool,
     screen_shake: f64,
     next_screen_shake: f64,
     camera_center: vec2<f32>,
+    transition: Option<geng::state::Transition>,
+    /// Set by the pause menu pushed on top of this state
+    pause_action: Rc<std::cell::Cell<Option<pause::PauseAction>>>,
+    /// Leaderboard after this run was submitted to it
+    leaderboard: Option<(highscores::Leaderboard, highscores::Placement)>,
+    /// Short message with the time it stays for
+    notice: Option<(String, f64)>,
 }
 
 impl Game {
@@ -77,109 +110,120 @@ impl Game {
     fn start_music(&mut self) {
         self.stop_music();
         let mut music = self.ctx.assets.music.effect();
-        music.set_volume(self.ctx.assets.config.music_volume);
+        music.set_volume(options::Options::load(&self.ctx.assets.config).music_volume);
         music.play();
         self.music = Some(music);
     }
-    pub fn new(ctx: &Context) -> Self {
+    pub fn new(
+        ctx: &Context,
+        level: Option<usize>,
+        difficulty: Difficulty,
+        roles: &[Role],
+    ) -> anyhow::Result<Self> {
         ctx.assets.sfx.start.play();
 
-        let mut map = Map::parse(&ctx.assets.map);
-        let (pos, _) = map
-            .iter()
-            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
-            .choose(&mut thread_rng())
-            .unwrap();
-        let mut id_gen = IdGen::new();
-        let snake_id = id_gen.gen();
-        map[pos] = MapCell::SnakePart {
-            snake_id,
-            segment_index: 0,
+        let playback = ctx.cli.replay.as_ref().map(Replay::load).transpose()?;
+        let (seed, difficulty, roles, map_source) = match &playback {
+            Some(replay) => (
+                replay.seed,
+                replay.difficulty,
+                replay.roles.clone(),
+                replay.map.clone(),
+            ),
+            None => (
+                ctx.cli.seed.unwrap_or_else(|| thread_rng().gen()),
+                difficulty,
+                roles
+                    .iter()
+                    .copied()
+                    .take(ctx.controls.borrow().max_players())
+                    .collect(),
+                match level {
+                    Some(level) => ctx.levels[level].map.clone(),
+                    None => ctx.assets.map.clone(),
+                },
+            ),
         };
+        let map = Map::parse(&map_source)?;
+        let config = ctx.assets.config.for_map(&map.header, difficulty);
+        let sim = Simulation::new(Rc::new(config), map, seed, &roles);
+        let mut game = Self::with_sim(ctx, level, difficulty, sim);
+        game.replay = Replay::new(seed, difficulty, &roles, &map_source);
+        game.playback = playback.map(replay::Playback::new);
+        Ok(game)
+    }
 
-        let camera_center = map.size().map(|x| x as f32) / 2.0;
+    /// Join a game hosted by [server]
+    pub fn connect(ctx: &Context, addr: &str) -> anyhow::Result<Self> {
+        let (remote, welcome) = net::Client::connect(addr)?;
+        let mut game = Self::mirror(ctx, welcome)?;
+        game.remote = Some(remote);
+        Ok(game)
+    }
 
+    /// Mirror of the server's game, without a connection yet
+    fn mirror(ctx: &Context, welcome: net::Welcome) -> anyhow::Result<Self> {
+        ctx.assets.sfx.start.play();
+        let difficulty = welcome.difficulty;
+        let sim = welcome.simulation(&ctx.assets.config)?;
+        Ok(Self::with_sim(ctx, None, difficulty, sim))
+    }
+
+    fn with_sim(
+        ctx: &Context,
+        level: Option<usize>,
+        difficulty: Difficulty,
+        sim: Simulation,
+    ) -> Self {
+        let camera_center = sim.map.size().map(|x| x as f32) / 2.0;
+        let roles: Vec<Role> = sim.players.iter().map(|player| player.role).collect();
+        // Only local games are recorded, see [Self::new]
+        let replay = Replay::new(sim.seed, difficulty, &roles, "");
         Self {
             screen_shake: 0.5,
             next_screen_shake: 0.0,
             particles: default(),
-            id_gen,
             ctx: ctx.clone(),
+            level,
+            difficulty,
             camera_center,
             camera: Camera2d {
                 center: camera_center,
                 rotation: Angle::ZERO,
-                fov: map.size().y as f32 + ctx.assets.config.camera_margin * 2.0,
-            },
-            map,
-            ai_state: HashMap::new(),
-            next_snake_move: {
-                let mut res = HashMap::new();
-                res.insert(snake_id, ctx.assets.config.snake_wake_up_time);
-                res
-            },
-            next_player_move: 0.0,
-            next_item: 0.0,
-            held_item: None,
-            player_id: None,
-            snake_speed_modifier: default(),
-            results: None,
-            snake_grow: {
-                let mut res = HashMap::new();
-                res.insert(snake_id, ctx.assets.config.start_snake_size - 1);
-                res
+                fov: sim.map.size().y as f32 + ctx.assets.config.camera_margin * 2.0,
             },
-            snake_reversing: default(),
-            alternate_move: 0,
-            time: 0.0,
-            player_moved: false,
-            score: 0,
+            versus: sim.is_versus().then(Versus::default),
+            sim,
+            inputs: vec![default(); roles.len()],
+            tick_time: 0.0,
+            replay,
+            playback: None,
+            remote: None,
             music: None,
             show_ui: true,
+            transition: None,
+            pause_action: default(),
+            leaderboard: None,
+            notice: None,
         }
     }
 
-    pub fn spawn_player(&mut self) -> Id {
-        let id = self.id_gen.gen();
-        let (pos, _) = self
-            .map
-            .iter()
-            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
-            .choose(&mut thread_rng())
-            .unwrap();
-        self.map[pos] = MapCell::Player(id);
-        id
-    }
-
-    pub fn spawn_item(&mut self) {
-        let num_items = self
-            .map
-            .iter()
-            .filter(|(_, cell)| matches!(cell, MapCell::Item(_)))
-            .count();
-        if num_items >= self.ctx.assets.config.max_items {
-            return;
+    /// Resume a saved run
+    pub fn load(ctx: &Context, save: SaveGame) -> anyhow::Result<Self> {
+        ctx.assets.sfx.start.play();
+        let difficulty = save.replay.difficulty;
+        let map = Map::parse(&save.replay.map)?;
+        let config = ctx.assets.config.for_map(&map.header, difficulty);
+        let sim = Simulation::restore(Rc::new(config), map, save.snapshot);
+        // The campaign could have changed since
+        let level = save.level.filter(|&level| level < ctx.levels.len());
+        let mut game = Self::with_sim(ctx, level, difficulty, sim);
+        game.replay = save.replay;
+        game.versus = save.versus;
+        if game.sim.player_moved && game.sim.results.is_none() {
+            game.start_music();
         }
-        let (pos, _) = self
-            .map
-            .iter()
-            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
-            .choose(&mut thread_rng())
-            .unwrap();
-        let weights = &self.ctx.assets.config.weights;
-        self.map[pos] = MapCell::Item(
-            [
-                (weights.food, Item::Food),
-                (weights.reverse, Item::Reverse),
-                (weights.snake_speed_up, Item::SnakeSpeedUp),
-                (weights.snake_speed_down, Item::SnakeSpeedDown),
-                (weights.snake_split, Item::SnakeSplit),
-            ]
-            .choose_weighted(&mut thread_rng(), |&(weight, _)| weight)
-            .unwrap()
-            .1
-            .clone(),
-        );
+        Ok(game)
     }
 
     fn hovered_cell(&self) -> Option<vec2<usize>> {
@@ -190,7 +234,7 @@ impl Game {
             );
             let hovered_pos = hovered_pos.map(|x| (x + 0.5).floor() as i32);
             if Aabb2::ZERO
-                .extend_positive(self.map.size().map(|x| x as i32))
+                .extend_positive(self.sim.map.size().map(|x| x as i32))
                 .contains(hovered_pos)
             {
                 return Some(hovered_pos.map(|x| x as usize));
@@ -199,167 +243,407 @@ impl Game {
         None
     }
 
-    fn move_player(&mut self, dir: vec2<isize>) {
-        if self.next_player_move > 0.0 {
-            return;
+    fn item_texture(&self, item: &Item) -> Rc<ugli::Texture> {
+        let textures = &self.ctx.assets.textures;
+        match item {
+            Item::Food => &textures.food,
+            Item::Reverse => &textures.reverse,
+            Item::SnakeSpeedUp => &textures.speedup,
+            Item::SnakeSpeedDown => &textures.speeddown,
+            Item::SnakeSplit => &textures.split,
+            Item::Freeze => &textures.freeze,
+            Item::Wall => &textures.wall,
+            Item::Decoy => &textures.decoy,
+            Item::Dash => &textures.dash,
+            Item::Teleport => &textures.teleport,
         }
-        if !self.player_moved {
-            self.player_moved = true;
-            self.start_music();
+        .clone()
+    }
+
+    /// Color to tell players apart by
+    fn player_tint(&self, id: Id) -> Rgba<f32> {
+        let tints = &self.ctx.assets.config.colors.player_tints;
+        match self.sim.player_index(id) {
+            Some(index) if !tints.is_empty() => tints[index % tints.len()],
+            _ => Rgba::WHITE,
         }
-        self.next_player_move = 1.0 / self.ctx.assets.config.player_speed;
-        let player_pos = self.map.iter().find(|(_, cell)| {
-            if let MapCell::Player(id) = cell {
-                Some(*id) == self.player_id
-            } else {
-                false
+    }
+
+    fn roles(&self) -> Vec<Role> {
+        self.sim.players.iter().map(|player| player.role).collect()
+    }
+
+    /// Controls of everyone playing
+    fn player_controls(&self) -> Vec<PlayerControls> {
+        let controls = self.ctx.controls.borrow();
+        (0..self.sim.players.len())
+            .map(|index| match &self.remote {
+                // The other players are on other machines
+                Some(remote) if index == remote.player => controls.player(0),
+                Some(_) => PlayerControls::default(),
+                None => controls.player(index),
+            })
+            .collect()
+    }
+
+    /// Player that a key press goes to.
+    ///
+    /// When several players have the key bound the last one gets it,
+    /// so solo play can use the keys of the other players too.
+    fn key_owner(players: &[PlayerControls], key: geng::Key) -> Option<usize> {
+        players.iter().rposition(|player| player.contains(key))
+    }
+
+    fn held_dirs(&self, player: usize) -> Vec<vec2<isize>> {
+        let players = self.player_controls();
+        let window = self.ctx.geng.window();
+        let mut dirs = Vec::new();
+        for (keys, dir) in players[player].dirs() {
+            let held = keys.iter().any(|&key| {
+                window.is_key_pressed(key) && Self::key_owner(&players, key) == Some(player)
+            });
+            if held {
+                dirs.push(dir);
             }
-        });
-        if let Some((pos, _)) = player_pos {
-            let new_pos = self.map.add_dir(pos, dir);
-            match &self.map[new_pos] {
-                MapCell::Empty => {}
-                MapCell::Item(item) => {
-                    if matches!(item, Item::Food) {
-                        return;
-                    }
-                    if self.held_item.is_some() {
-                        return;
-                    }
-                    self.ctx.assets.sfx.pickup.play();
-                    self.held_item = Some(item.clone());
+        }
+        // Gamepads drive the first player on this machine
+        if player == self.remote.as_ref().map_or(0, |remote| remote.player) {
+            let controls = self.ctx.controls.borrow();
+            for dir in self.ctx.gamepads.borrow().held_dirs(&controls) {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
                 }
-                _ => return,
             }
-            let cell = mem::take(&mut self.map[pos]);
-            self.map[new_pos] = cell;
         }
+        dirs
     }
 
-    fn snake_ids(&self) -> HashSet<Id> {
-        self.map
-            .iter()
-            .filter_map(|(_, cell)| {
-                if let MapCell::SnakePart { snake_id, .. } = cell {
-                    Some(*snake_id)
+    fn handle_sim_event(&mut self, event: simulation::Event) {
+        match event {
+            simulation::Event::Started => {
+                self.start_music();
+            }
+            simulation::Event::ItemPickedUp(_) => {
+                self.ctx.assets.sfx.pickup.play();
+            }
+            simulation::Event::ItemUsed { pos, item } => {
+                self.spawn_particle_batch(pos, self.item_texture(&item));
+                match item {
+                    Item::Food => self.ctx.assets.sfx.eat.play(),
+                    _ => self.ctx.assets.sfx.use_item.play(),
+                };
+            }
+            simulation::Event::SnakeSped { pos, multiplier } => {
+                let texture = if multiplier > 1.0 {
+                    &self.ctx.assets.textures.speedup
                 } else {
-                    None
-                }
-            })
-            .collect()
-    }
-
-    fn use_item(&mut self, snake_id: Option<Id>, item: Item) {
-        // TODO: instead of rng closest to player maybe"????
-        let snake_id = snake_id.or(self.snake_ids().into_iter().choose(&mut thread_rng()));
-        let Some(id) = snake_id else { return };
-        self.score += 1;
-        match item {
-            Item::Food => {
-                *self.snake_grow.entry(id).or_default() += self.ctx.assets.config.food_value - 1;
-                self.ctx.assets.sfx.eat.play();
-            }
-            Item::Reverse => {
-                self.ctx.assets.sfx.use_item.play();
-                let head_idx = match self.map[snake::head(id, &self.map)] {
-                    MapCell::SnakePart {
-                        snake_id,
-                        segment_index,
-                    } if snake_id == id => segment_index,
-                    _ => unreachable!(),
+                    &self.ctx.assets.textures.speeddown
                 };
-                // for (_pos, cell) in self.map.iter_mut() {
-                //     match *cell {
-                //         MapCell::SnakePart {
-                //             snake_id,
-                //             segment_index,
-                //         } if snake_id == id => {
-                //             *cell = MapCell::SnakePart {
-                //                 snake_id,
-                //                 segment_index: head_idx - segment_index,
-                //             }
-                //         }
-                //         _ => {}
-                //     }
-                // }
-                if head_idx > 0 {
-                    self.snake_reversing.insert(id, head_idx - 1);
+                self.spawn_particle(pos, texture.clone());
+            }
+            simulation::Event::SnakeDied { positions } => {
+                self.ctx.assets.sfx.ded.play();
+                self.screen_shake = 1.0;
+                for pos in positions {
+                    let tex = self.ctx.assets.textures.snek.clone();
+                    self.spawn_particle(pos, tex);
                 }
-                self.ai_state.remove(&id);
             }
-            Item::SnakeSpeedUp => {
-                self.ctx.assets.sfx.use_item.play();
-                self.snake_speed_modifier.insert(
-                    id,
-                    SnakeSpeedModifier {
-                        time_left: self.ctx.assets.config.items.snake_speed.time,
-                        multiplier: self.ctx.assets.config.items.snake_speed.multiplier,
-                    },
-                );
+            simulation::Event::PlayerCaught { .. } => {
+                self.screen_shake = 0.5;
             }
-            Item::SnakeSpeedDown => {
-                self.ctx.assets.sfx.use_item.play();
-                self.snake_speed_modifier.insert(
-                    id,
-                    SnakeSpeedModifier {
-                        time_left: self.ctx.assets.config.items.snake_speed.time,
-                        multiplier: 1.0 / self.ctx.assets.config.items.snake_speed.multiplier,
-                    },
-                );
+            simulation::Event::Lost => {
+                self.show_ui = true;
+                self.stop_music();
+                self.ctx.assets.sfx.end.play();
+                if let Some(versus) = &mut self.versus {
+                    versus.snake_wins += 1;
+                }
+                self.finish_run();
             }
-            Item::SnakeSplit => {
-                self.ctx.assets.sfx.use_item.play();
-                let mut min_max: HashMap<Id, (u32, u32)> = HashMap::new();
-                for (_pos, cell) in self.map.iter() {
-                    if let MapCell::SnakePart {
-                        snake_id,
-                        segment_index,
-                    } = *cell
-                    {
-                        let cur = min_max
-                            .entry(snake_id)
-                            .or_insert((segment_index, segment_index));
-                        cur.0 = cur.0.min(segment_index);
-                        cur.1 = cur.1.max(segment_index);
+            simulation::Event::Won => {
+                self.show_ui = true;
+                self.stop_music();
+                self.ctx.assets.sfx.start.play();
+                if let Some(versus) = &mut self.versus {
+                    versus.runner_wins += 1;
+                }
+                if self.playback.is_none() && self.remote.is_none() && self.versus.is_none() {
+                    if let Some(level) = self.level {
+                        campaign::Progress::load().complete(level);
                     }
                 }
-                let new_snake_ids: HashMap<Id, Id> = min_max
-                    .keys()
-                    .copied()
-                    .map(|id| (id, self.id_gen.gen()))
-                    .collect();
-                self.next_snake_move.insert(
-                    new_snake_ids[&id],
-                    self.ctx.assets.config.snake_wake_up_time,
+                self.finish_run();
+            }
+        }
+    }
+
+    fn finish_run(&mut self) {
+        // Only the server knows the whole run
+        if self.playback.is_some() || self.remote.is_some() {
+            return;
+        }
+        self.save_replay();
+        // Rounds against another player do not go into the leaderboards
+        if self.versus.is_some() {
+            return;
+        }
+        let results = self.sim.results.as_ref().unwrap();
+        let mut map_key = match self.level {
+            Some(level) => self.ctx.levels[level].file.clone(),
+            None => "endless".to_owned(),
+        };
+        // Co-op runs are not comparable with solo ones
+        if self.sim.players.len() > 1 {
+            map_key += &format!(" ({} players)", self.sim.players.len());
+        }
+        self.leaderboard = Some(highscores::Highscores::submit(
+            &map_key,
+            self.difficulty,
+            highscores::Record {
+                score: results.score,
+                time: results.time,
+                seed: self.sim.seed,
+            },
+            self.ctx.assets.config.highscore_entries,
+        ));
+    }
+
+    fn draw_leaderboard(
+        &self,
+        framebuffer: &mut ugli::Framebuffer,
+        camera: &Camera2d,
+        leaderboard: &highscores::Leaderboard,
+        placement: &highscores::Placement,
+    ) {
+        let config = &self.ctx.assets.config;
+        if placement.is_record() {
+            self.ctx.assets.font.draw_with_outline(
+                framebuffer,
+                camera,
+                "NEW RECORD!",
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(vec2(0.0, 1.2)) * mat3::scale_uniform(0.6),
+                config.colors.player,
+                config.outline_size,
+                config.outline_color,
+            );
+        }
+        let columns: [(f32, &str, Option<usize>, Vec<String>); 2] = [
+            (
+                -6.0,
+                "best scores",
+                placement.by_score,
+                leaderboard
+                    .by_score
+                    .iter()
+                    .map(|record| record.score.to_string())
+                    .collect(),
+            ),
+            (
+                6.0,
+                "longest survival",
+                placement.by_time,
+                leaderboard
+                    .by_time
+                    .iter()
+                    .map(|record| format_time(record.time))
+                    .collect(),
+            ),
+        ];
+        for (x, title, place, records) in columns {
+            self.ctx.assets.font.draw_with_outline(
+                framebuffer,
+                camera,
+                title,
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(vec2(x, 0.5)) * mat3::scale_uniform(0.4),
+                Rgba::WHITE,
+                config.outline_size,
+                config.outline_color,
+            );
+            for (i, record) in records.iter().enumerate() {
+                let color = if place == Some(i) {
+                    config.colors.player
+                } else {
+                    Rgba::WHITE
+                };
+                self.ctx.assets.font.draw_with_outline(
+                    framebuffer,
+                    camera,
+                    &format!("{}. {}", i + 1, record),
+                    vec2::splat(geng::TextAlign::CENTER),
+                    mat3::translate(vec2(x, -0.2 - i as f32 * 0.5)) * mat3::scale_uniform(0.35),
+                    color,
+                    config.outline_size,
+                    config.outline_color,
                 );
-                for (_pos, cell) in self.map.iter_mut() {
-                    if let MapCell::SnakePart {
-                        snake_id,
-                        segment_index,
-                    } = cell
-                    {
-                        if *snake_id == id
-                            && *segment_index < (min_max[snake_id].1 + min_max[snake_id].0) / 2
-                        {
-                            *snake_id = new_snake_ids[snake_id];
-                        }
+            }
+        }
+    }
+
+    fn next_level(&self) -> Option<usize> {
+        let next = self.level? + 1;
+        (next < self.ctx.levels.len()).then_some(next)
+    }
+
+    fn restart(&mut self, level: Option<usize>) {
+        if let Some(remote) = &mut self.remote {
+            if let Err(e) = remote.request_restart() {
+                log::error!("Failed to request a restart: {e}");
+            }
+            return;
+        }
+        match Self::new(&self.ctx, level, self.difficulty, &self.roles()) {
+            Ok(game) => self.continue_with(game),
+            Err(e) => {
+                self.transition = Some(geng::state::Transition::Switch(Box::new(ErrorScreen::new(
+                    &self.ctx, e,
+                ))))
+            }
+        }
+    }
+
+    /// Switch to a new round, keeping the score of an unfinished versus match
+    fn continue_with(&mut self, mut game: Self) {
+        if let Some(versus) = &self.versus {
+            if !versus.is_over(&self.ctx.assets.config.versus) {
+                game.versus = Some(versus.clone());
+            }
+        }
+        *self = game;
+    }
+
+    /// Send local input to the server and mirror what it sent back
+    fn update_remote(&mut self) {
+        let Some(player) = self.remote.as_ref().map(|remote| remote.player) else {
+            return;
+        };
+        let mut input = mem::take(&mut self.inputs[player]);
+        input.held_dirs = self.held_dirs(player);
+        let remote = self.remote.as_mut().unwrap();
+        let messages = remote.send_input(input).and_then(|()| remote.receive());
+        let messages = match messages {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.transition = Some(geng::state::Transition::Switch(Box::new(
+                    ErrorScreen::new(&self.ctx, e),
+                )));
+                return;
+            }
+        };
+        for message in messages {
+            match message {
+                net::ServerMessage::Welcome(welcome) => match Self::mirror(&self.ctx, welcome) {
+                    Ok(mut game) => {
+                        game.remote = self.remote.take();
+                        self.continue_with(game);
+                    }
+                    Err(e) => log::error!("Failed to restart: {e:#}"),
+                },
+                net::ServerMessage::Waiting { .. } => {}
+                net::ServerMessage::Update(update) => {
+                    for event in update.apply(&mut self.sim) {
+                        self.handle_sim_event(event);
                     }
                 }
             }
         }
     }
 
-    fn results(&self) -> Results {
-        Results {
-            time: self.time,
-            win: self.map.iter().any(|(_, cell)| {
-                if let MapCell::Player(id) = *cell {
-                    Some(id) == self.player_id
-                } else {
-                    false
+    /// Path in `dir` named after the current time and seed
+    fn timestamped_path(&self, dir: &str) -> std::path::PathBuf {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        run_dir()
+            .join(dir)
+            .join(format!("{timestamp}-{}.json", self.sim.seed))
+    }
+
+    fn show_notice(&mut self, text: &str) {
+        self.notice = Some((text.to_owned(), NOTICE_TIME));
+    }
+
+    /// Write the run so far to `path`, see [SaveGame]
+    fn save_game(&mut self, path: &std::path::Path) {
+        if self.playback.is_some()
+            || self.remote.is_some()
+            || self.ctx.cli.editor
+            || self.sim.results.is_some()
+        {
+            self.show_notice("Can not save now");
+            return;
+        }
+        let snapshot = self.sim.snapshot();
+        // Playing the replay back has to reset the random generator at the same tick
+        self.replay
+            .reseeds
+            .push((self.sim.ticks, snapshot.rng_seed()));
+        let save = SaveGame {
+            level: self.level,
+            replay: self.replay.clone(),
+            versus: self.versus.clone(),
+            snapshot,
+        };
+        match save.save(path) {
+            Ok(()) => {
+                log::info!("Game saved to {path:?}");
+                self.show_notice("Game saved");
+            }
+            Err(e) => {
+                log::error!("Failed to save the game: {e}");
+                self.show_notice("Failed to save the game");
+            }
+        }
+    }
+
+    fn quickload(&mut self) {
+        if self.playback.is_some() || self.remote.is_some() || self.ctx.cli.editor {
+            return;
+        }
+        let save = SaveGame::load(SaveGame::quicksave_path());
+        match save.and_then(|save| Self::load(&self.ctx, save)) {
+            Ok(mut game) => {
+                game.show_notice("Game loaded");
+                *self = game;
+            }
+            Err(e) => {
+                log::error!("Failed to quickload: {e:#}");
+                self.show_notice("No quicksave to load");
+            }
+        }
+    }
+
+    fn save_replay(&self) {
+        let path = self.timestamped_path("replays");
+        match self.replay.save(&path) {
+            Ok(()) => log::info!("Replay saved to {path:?}"),
+            Err(e) => log::error!("Failed to save replay: {e}"),
+        }
+    }
+
+    fn handle_pause_action(&mut self, action: pause::PauseAction) {
+        match action {
+            pause::PauseAction::Resume => {
+                // Volume could have been changed in the options
+                if let Some(music) = &mut self.music {
+                    music.set_volume(options::Options::load(&self.ctx.assets.config).music_volume);
                 }
-            }),
-            score: self.score,
+            }
+            pause::PauseAction::Save => self.save_game(&self.timestamped_path("saves")),
+            pause::PauseAction::Restart => self.restart(self.level),
+            pause::PauseAction::Quit => {
+                // Everyone else of a networked game stays on their own machines
+                let roles = match self.remote {
+                    Some(_) => vec![Role::Runner],
+                    None => self.roles(),
+                };
+                self.transition = Some(geng::state::Transition::Switch(Box::new(
+                    LevelSelect::new(&self.ctx, &roles),
+                )));
+            }
         }
     }
 }
@@ -371,7 +655,22 @@ impl Drop for Game {
 }
 
 impl geng::State for Game {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
     fn update(&mut self, delta_time: f64) {
+        if let Some(action) = self.pause_action.take() {
+            self.handle_pause_action(action);
+        }
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+        if let Some((_, time_left)) = &mut self.notice {
+            *time_left -= delta_time;
+            if *time_left < 0.0 {
+                self.notice = None;
+            }
+        }
         self.screen_shake -= delta_time;
         if self.screen_shake > 0.0 {
             self.next_screen_shake -= delta_time;
@@ -387,238 +686,67 @@ impl geng::State for Game {
             particle.pos += particle.vel * delta_time as f32;
         }
         self.particles.retain(|p| p.t < 1.0);
-        if self.player_moved {
-            self.time += delta_time;
+
+        if self.ctx.cli.editor {
+            return;
         }
-        if !self.ctx.cli.editor && self.results.is_none() {
-            if self.player_id.is_none() {
-                self.player_id = Some(self.spawn_player());
-            } else {
-                if !self.map.iter().any(|(_, cell)| {
-                    if let MapCell::Player(id) = *cell {
-                        Some(id) == self.player_id
-                    } else {
-                        false
-                    }
-                }) {
-                    self.results = Some(self.results());
-                    self.screen_shake = 0.5;
-                    self.show_ui = true;
-                    self.stop_music();
-                    self.ctx.assets.sfx.end.play();
-                }
-            }
+        if self.remote.is_some() {
+            self.update_remote();
+            return;
         }
-
-        if self.player_moved {
-            for id in self.snake_ids() {
-                if let Some(modifier) = self.snake_speed_modifier.get_mut(&id) {
-                    modifier.time_left -= delta_time;
-                    if modifier.time_left < 0.0 {
-                        self.snake_speed_modifier.remove(&id);
-                    }
+        self.tick_time += delta_time;
+        let tick_delta = 1.0 / self.ctx.assets.config.ticks_per_second;
+        while self.tick_time >= tick_delta {
+            self.tick_time -= tick_delta;
+            let inputs = if let Some(playback) = &mut self.playback {
+                if let Some(rng_seed) = playback.reseed(self.sim.ticks) {
+                    self.sim.reseed(rng_seed);
                 }
-                let next_move = self.next_snake_move.entry(id).or_default();
-                *next_move -= delta_time;
-                if *next_move < 0.0 {
-                    if let Some(next_eat_index) = self.snake_reversing.remove(&id) {
-                        *next_move = 1.0 / self.ctx.assets.config.snake_reverse_speed;
-                        let head_pos = snake::head(id, &self.map);
-                        let head_index = match self.map[head_pos] {
-                            MapCell::SnakePart {
-                                snake_id,
-                                segment_index,
-                            } if snake_id == id => segment_index,
-                            _ => unreachable!(),
-                        };
-                        let next =
-                            self.map
-                                .neighbors(head_pos)
-                                .find(|&next| match self.map[next] {
-                                    MapCell::SnakePart {
-                                        snake_id,
-                                        segment_index,
-                                    } => snake_id == id && segment_index == next_eat_index,
-                                    _ => false,
-                                });
-                        if let Some(next) = next {
-                            self.map[next] = MapCell::SnakePart {
-                                snake_id: id,
-                                segment_index: head_index + 1,
-                            };
-                            if next_eat_index > 0 {
-                                self.snake_reversing.insert(id, next_eat_index - 1);
-                            }
-                        }
-                    } else {
-                        *next_move = 1.0
-                            / self.ctx.assets.config.snake_speed
-                            / self
-                                .snake_speed_modifier
-                                .get(&id)
-                                .map_or(1.0, |modifier| modifier.multiplier);
-
-                        if let Some(modifier) = self.snake_speed_modifier.get(&id) {
-                            let texture = if modifier.multiplier > 1.0 {
-                                &self.ctx.assets.textures.speedup
-                            } else {
-                                &self.ctx.assets.textures.speeddown
-                            };
-                            self.spawn_particle(snake::head(id, &self.map), texture.clone());
-                        }
-                        let textures = &self.ctx.assets.textures;
-
-                        let snake_grow = self.snake_grow.entry(id).or_default();
-                        match snake::go_ai(
-                            id,
-                            &self.ctx.assets.config,
-                            &mut self.map,
-                            self.ai_state.entry(id).or_default(),
-                            *snake_grow == 0,
-                        ) {
-                            Ok(Some(item)) => {
-                                self.spawn_particle_batch(
-                                    snake::head(id, &self.map),
-                                    match item {
-                                        Item::Food => &textures.food,
-                                        Item::Reverse => &textures.reverse,
-                                        Item::SnakeSpeedUp => &textures.speedup,
-                                        Item::SnakeSpeedDown => &textures.speeddown,
-                                        Item::SnakeSplit => &textures.split,
-                                    }
-                                    .clone(),
-                                );
-                                self.use_item(Some(id), item);
-                                self.spawn_item();
-                            }
-                            Ok(None) => {}
-                            Err(()) => {
-                                self.ctx.assets.sfx.ded.play();
-                                self.screen_shake = 1.0;
-                                let mut explosion_positions = Vec::new();
-                                for (pos, cell) in self.map.iter_mut() {
-                                    if let MapCell::SnakePart { snake_id, .. } = *cell {
-                                        if snake_id == id {
-                                            *cell = MapCell::Empty;
-                                            explosion_positions.push(pos);
-                                        }
-                                    }
-                                }
-                                for pos in explosion_positions {
-                                    let tex = self.ctx.assets.textures.snek.clone();
-                                    self.spawn_particle(pos, tex);
-                                }
-                            }
-                        }
-                        let snake_grow = self.snake_grow.entry(id).or_default();
-                        if *snake_grow > 0 {
-                            *snake_grow -= 1;
-                        }
-                    }
+                playback.inputs(self.sim.ticks)
+            } else {
+                let mut inputs =
+                    mem::replace(&mut self.inputs, vec![default(); self.sim.players.len()]);
+                for (player, input) in inputs.iter_mut().enumerate() {
+                    input.held_dirs = self.held_dirs(player);
                 }
+                inputs
+            };
+            self.replay.record(self.sim.ticks, &inputs);
+            for event in self.sim.tick(tick_delta, &inputs) {
+                self.handle_sim_event(event);
             }
-
-            self.next_item -= delta_time;
-            if self.next_item < 0.0 {
-                self.next_item = self.ctx.assets.config.new_item_time;
-                self.spawn_item();
-            }
-        }
-
-        self.next_player_move -= delta_time;
-        if self.next_player_move < 0.0 {
-            let mut dir = Vec::new();
-            if self
-                .ctx
-                .assets
-                .config
-                .controls
-                .left
-                .iter()
-                .any(|&key| self.ctx.geng.window().is_key_pressed(key))
-            {
-                dir.push(vec2(-1, 0));
-            }
-            if self
-                .ctx
-                .assets
-                .config
-                .controls
-                .right
-                .iter()
-                .any(|&key| self.ctx.geng.window().is_key_pressed(key))
-            {
-                dir.push(vec2(1, 0));
-            }
-            if self
-                .ctx
-                .assets
-                .config
-                .controls
-                .up
-                .iter()
-                .any(|&key| self.ctx.geng.window().is_key_pressed(key))
-            {
-                dir.push(vec2(0, 1));
-            }
-            if self
-                .ctx
-                .assets
-                .config
-                .controls
-                .down
-                .iter()
-                .any(|&key| self.ctx.geng.window().is_key_pressed(key))
-            {
-                dir.push(vec2(0, -1));
-            }
-            if !dir.is_empty() {
-                let dir = dir[self.alternate_move % dir.len()];
-                self.move_player(dir);
-            }
-            self.alternate_move += 1;
         }
     }
     fn handle_event(&mut self, event: geng::Event) {
-        match event {
-            geng::Event::KeyPress { key }
-                if self.ctx.assets.config.controls.use_item.contains(&key) =>
-            {
-                if let Some(item) = self.held_item.take() {
-                    let player_pos = self.map.iter().find(|(_, cell)| {
-                        if let MapCell::Player(id) = cell {
-                            self.player_id == Some(*id)
-                        } else {
-                            false
-                        }
-                    });
-                    if let Some((player_pos, _)) = player_pos {
-                        let snake_id = self.snake_ids().into_iter().min_by_key(|&id| {
-                            self.map.distance(snake::head(id, &self.map), player_pos)
-                        });
-                        if let Some(snake_id) = snake_id {
-                            let textures = &self.ctx.assets.textures;
-                            self.spawn_particle_batch(
-                                snake::head(snake_id, &self.map),
-                                match item {
-                                    Item::Food => &textures.food,
-                                    Item::Reverse => &textures.reverse,
-                                    Item::SnakeSpeedUp => &textures.speedup,
-                                    Item::SnakeSpeedDown => &textures.speeddown,
-                                    Item::SnakeSplit => &textures.split,
-                                }
-                                .clone(),
-                            );
-                            self.use_item(Some(snake_id), item);
-                        }
+        let controls = self.ctx.controls.borrow().clone();
+        let players = self.player_controls();
+        // Editor shortcuts are pressed with Ctrl, so keys like Ctrl+S do not move anyone
+        let editor_shortcut = self.ctx.cli.editor
+            && self
+                .ctx
+                .geng
+                .window()
+                .is_key_pressed(geng::Key::ControlLeft);
+        if let geng::Event::KeyPress { key } = event {
+            if let Some(player) = Self::key_owner(&players, key).filter(|_| !editor_shortcut) {
+                let input = &mut self.inputs[player];
+                if players[player].use_item.contains(&key) {
+                    input.use_item = true;
+                }
+                for (keys, dir) in players[player].dirs() {
+                    if keys.contains(&key) {
+                        input.moves.push(dir);
                     }
                 }
+                return;
             }
+        }
+        match event {
             geng::Event::MousePress {
                 button: geng::MouseButton::Left,
             } if self.ctx.cli.editor => {
                 if let Some(pos) = self.hovered_cell() {
-                    self.map[pos] = MapCell::Wall;
+                    self.sim.map.set(pos, MapCell::Wall);
                 }
             }
             geng::Event::CursorMove { .. } if self.ctx.cli.editor => {
@@ -629,7 +757,7 @@ impl geng::State for Game {
                         .window()
                         .is_button_pressed(geng::MouseButton::Left)
                     {
-                        self.map[pos] = MapCell::Wall;
+                        self.sim.map.set(pos, MapCell::Wall);
                     }
                     if self
                         .ctx
@@ -637,7 +765,7 @@ impl geng::State for Game {
                         .window()
                         .is_button_pressed(geng::MouseButton::Right)
                     {
-                        self.map[pos] = MapCell::Empty;
+                        self.sim.map.set(pos, MapCell::Empty);
                     }
                 }
             }
@@ -645,22 +773,55 @@ impl geng::State for Game {
                 button: geng::MouseButton::Right,
             } if self.ctx.cli.editor => {
                 if let Some(pos) = self.hovered_cell() {
-                    self.map[pos] = MapCell::Empty;
+                    self.sim.map.set(pos, MapCell::Empty);
                 }
             }
-            geng::Event::KeyPress { key: geng::Key::F } => {
+            geng::Event::KeyPress { key } if controls.fullscreen.contains(&key) => {
                 self.ctx.geng.window().toggle_fullscreen();
             }
-            geng::Event::KeyPress { key: geng::Key::M } => {
+            geng::Event::KeyPress { key } if controls.toggle_music.contains(&key) => {
                 self.toggle_music();
             }
-            geng::Event::KeyPress {
-                key: geng::Key::Tab,
-            } => {
+            geng::Event::KeyPress { key } if controls.toggle_ui.contains(&key) => {
                 self.show_ui = !self.show_ui;
             }
-            geng::Event::KeyPress { key: geng::Key::R } => {
-                *self = Self::new(&self.ctx);
+            geng::Event::KeyPress { key } if controls.quicksave.contains(&key) => {
+                self.save_game(&SaveGame::quicksave_path());
+            }
+            geng::Event::KeyPress { key } if controls.quickload.contains(&key) => {
+                self.quickload();
+            }
+            geng::Event::KeyPress { key } if controls.restart.contains(&key) => {
+                self.restart(self.level);
+            }
+            geng::Event::KeyPress { key }
+                if controls.next_level.contains(&key)
+                    && self.sim.results.as_ref().is_some_and(|results| results.win)
+                    && self.next_level().is_some() =>
+            {
+                self.restart(self.next_level());
+            }
+            geng::Event::KeyPress {
+                key: geng::Key::Escape,
+            } if self.remote.is_some() => {
+                // The server does not wait for anyone, so there is no pausing a networked game
+                if self
+                    .notice
+                    .as_ref()
+                    .is_some_and(|(text, _)| text == LEAVE_NOTICE)
+                {
+                    self.handle_pause_action(pause::PauseAction::Quit);
+                } else {
+                    self.show_notice(LEAVE_NOTICE);
+                }
+            }
+            geng::Event::KeyPress {
+                key: geng::Key::Escape,
+            } => {
+                self.transition = Some(geng::state::Transition::Push(Box::new(pause::Pause::new(
+                    &self.ctx,
+                    self.pause_action.clone(),
+                ))));
             }
             geng::Event::KeyPress { key: geng::Key::S }
                 if self
@@ -670,30 +831,14 @@ impl geng::State for Game {
                     .is_key_pressed(geng::Key::ControlLeft)
                     && self.ctx.cli.editor =>
             {
-                self.map.save(run_dir().join("assets").join("map.txt"));
+                if let Err(e) = self.sim.map.save(run_dir().join("assets").join("map.txt")) {
+                    log::error!("Failed to save map: {e}");
+                }
             }
             geng::Event::KeyPress {
                 key: geng::Key::Space,
             } if self.ctx.cli.editor => {
-                self.spawn_item();
-            }
-            geng::Event::KeyPress { key }
-                if self.ctx.assets.config.controls.left.contains(&key) =>
-            {
-                self.move_player(vec2(-1, 0));
-            }
-            geng::Event::KeyPress { key }
-                if self.ctx.assets.config.controls.right.contains(&key) =>
-            {
-                self.move_player(vec2(1, 0));
-            }
-            geng::Event::KeyPress { key } if self.ctx.assets.config.controls.up.contains(&key) => {
-                self.move_player(vec2(0, 1));
-            }
-            geng::Event::KeyPress { key }
-                if self.ctx.assets.config.controls.down.contains(&key) =>
-            {
-                self.move_player(vec2(0, -1));
+                self.sim.spawn_item();
             }
             _ => {}
         }
@@ -706,7 +851,8 @@ impl geng::State for Game {
             framebuffer,
             &self.camera,
             &draw2d::Quad::new(
-                Aabb2::point(vec2::splat(-0.5)).extend_positive(self.map.size().map(|x| x as f32)),
+                Aabb2::point(vec2::splat(-0.5))
+                    .extend_positive(self.sim.map.size().map(|x| x as f32)),
                 colors.background,
             ),
         );
@@ -716,6 +862,11 @@ impl geng::State for Game {
             Item::SnakeSpeedUp => colors.snake_speed_up,
             Item::SnakeSpeedDown => colors.snake_speed_down,
             Item::SnakeSplit => colors.snake_split,
+            Item::Freeze => colors.freeze,
+            Item::Wall => colors.wall_item,
+            Item::Decoy => colors.decoy,
+            Item::Dash => colors.dash,
+            Item::Teleport => colors.teleport,
         };
         let item_texture = |item: &Item| match item {
             Item::Food => &textures.food,
@@ -723,25 +874,51 @@ impl geng::State for Game {
             Item::SnakeSpeedUp => &textures.speedup,
             Item::SnakeSpeedDown => &textures.speeddown,
             Item::SnakeSplit => &textures.split,
+            Item::Freeze => &textures.freeze,
+            Item::Wall => &textures.wall,
+            Item::Decoy => &textures.decoy,
+            Item::Dash => &textures.dash,
+            Item::Teleport => &textures.teleport,
         };
         let snake_ends: HashMap<Id, (vec2<usize>, vec2<usize>)> = self
+            .sim
             .snake_ids()
             .into_iter()
-            .map(|id| (id, (snake::head(id, &self.map), snake::tail(id, &self.map))))
+            .map(|id| {
+                (
+                    id,
+                    (
+                        snake::head(id, &self.sim.map),
+                        snake::tail(id, &self.sim.map),
+                    ),
+                )
+            })
             .collect();
-        for (pos, cell) in self.map.iter() {
+        for (pos, cell) in self.sim.map.iter() {
             let color = match *cell {
-                MapCell::Wall => colors.wall,
+                MapCell::Wall => match self.sim.map.time_left(pos) {
+                    // Placed walls fade out as they crumble
+                    Some(time_left) => {
+                        let mut color = colors.wall_item;
+                        color.a *= (time_left / self.ctx.assets.config.items.wall.time)
+                            .clamp(0.0, 1.0) as f32;
+                        color
+                    }
+                    None => colors.wall,
+                },
+                MapCell::Portal(number) => colors.portals[number as usize % colors.portals.len()],
                 MapCell::SnakePart {
                     snake_id,
                     segment_index: idx,
                 } => {
-                    if pos == snake_ends[&snake_id].0 {
+                    if self.sim.snake_frozen.contains_key(&snake_id) {
+                        colors.freeze
+                    } else if pos == snake_ends[&snake_id].0 {
                         colors.snake_head
                     } else if pos == snake_ends[&snake_id].1 {
                         colors.snake_tail
                     } else {
-                        colors.snake[(match self.map[snake_ends[&snake_id].0] {
+                        colors.snake[(match self.sim.map[snake_ends[&snake_id].0] {
                             MapCell::SnakePart { segment_index, .. } => segment_index,
                             _ => unreachable!(),
                         } - idx) as usize
@@ -752,7 +929,7 @@ impl geng::State for Game {
             };
             let mut aabb = Aabb2::point(pos.map(|x| x as f32))
                 .extend_uniform(0.5 - self.ctx.assets.config.cell_margin);
-            let need_extend = |next: vec2<usize>| match (cell, &self.map[next]) {
+            let need_extend = |next: vec2<usize>| match (cell, &self.sim.map[next]) {
                 (MapCell::Wall, MapCell::Wall) => true,
                 (
                     &MapCell::SnakePart {
@@ -766,16 +943,16 @@ impl geng::State for Game {
                 ) if prev_id == next_id => prev + 1 == next || next + 1 == prev,
                 _ => false,
             };
-            if need_extend(self.map.add_dir(pos, vec2(-1, 0))) {
+            if need_extend(self.sim.map.add_dir(pos, vec2(-1, 0))) {
                 aabb = aabb.extend_left(self.ctx.assets.config.cell_margin);
             }
-            if need_extend(self.map.add_dir(pos, vec2(1, 0))) {
+            if need_extend(self.sim.map.add_dir(pos, vec2(1, 0))) {
                 aabb = aabb.extend_right(self.ctx.assets.config.cell_margin);
             }
-            if need_extend(self.map.add_dir(pos, vec2(0, -1))) {
+            if need_extend(self.sim.map.add_dir(pos, vec2(0, -1))) {
                 aabb = aabb.extend_down(self.ctx.assets.config.cell_margin);
             }
-            if need_extend(self.map.add_dir(pos, vec2(0, 1))) {
+            if need_extend(self.sim.map.add_dir(pos, vec2(0, 1))) {
                 aabb = aabb.extend_up(self.ctx.assets.config.cell_margin);
             }
             self.ctx.geng.draw2d().draw2d(
@@ -785,20 +962,26 @@ impl geng::State for Game {
             );
         }
 
-        for (pos, cell) in self.map.iter() {
-            let texture = match cell {
+        for (pos, cell) in self.sim.map.iter() {
+            let (texture, color) = match cell {
                 MapCell::SnakePart {
                     snake_id,
                     segment_index,
                 } => {
                     if pos == snake_ends[snake_id].0 {
-                        &textures.snek
+                        let tint = if self.sim.snake_frozen.contains_key(snake_id) {
+                            colors.freeze
+                        } else {
+                            self.player_tint(*snake_id)
+                        };
+                        (&textures.snek, tint)
                     } else {
                         continue;
                     }
                 }
-                MapCell::Player(_) => &textures.player,
-                MapCell::Item(item) => item_texture(item),
+                &MapCell::Player(id) => (&textures.player, self.player_tint(id)),
+                MapCell::Decoy => (&textures.player, colors.decoy),
+                MapCell::Item(item) => (item_texture(item), Rgba::WHITE),
                 _ => continue,
             };
             let aabb = Aabb2::point(pos.map(|x| x as f32))
@@ -806,7 +989,7 @@ impl geng::State for Game {
             self.ctx.geng.draw2d().draw2d(
                 framebuffer,
                 &self.camera,
-                &draw2d::TexturedQuad::new(aabb, &**texture),
+                &draw2d::TexturedQuad::colored(aabb, &**texture, color),
             );
         }
 
@@ -832,10 +1015,17 @@ impl geng::State for Game {
             rotation: Angle::ZERO,
             fov: self.ctx.assets.config.ui_fov,
         };
-        if let Some(item) = &self.held_item {
+        let player_controls = self.player_controls();
+        let player_count = self.sim.players.len();
+        for (index, player) in self.sim.players.iter().enumerate() {
+            let Some(item) = &player.held_item else {
+                continue;
+            };
+            // Held items of several players are spread along the top
+            let offset = (index as f32 - (player_count - 1) as f32 / 2.0) * 8.0;
             let pos = vec2(
-                self.map.size().x as f32 / 2.0,
-                self.map.size().y as f32 + 2.0,
+                self.sim.map.size().x as f32 / 2.0 + offset,
+                self.sim.map.size().y as f32 + 2.0,
             );
             self.ctx.geng.draw2d().draw2d(
                 framebuffer,
@@ -850,10 +1040,13 @@ impl geng::State for Game {
             self.ctx.assets.font.draw(
                 framebuffer,
                 &self.camera,
-                &"SPACE to use",
+                &format!(
+                    "{} to use",
+                    Controls::key_name(&player_controls[index].use_item)
+                ),
                 vec2::splat(geng::TextAlign::LEFT),
                 mat3::translate(pos + vec2(1.0, -0.5)),
-                Rgba::WHITE,
+                player.id.map_or(Rgba::WHITE, |id| self.player_tint(id)),
             );
         }
         if self.ctx.cli.editor {
@@ -869,12 +1062,16 @@ impl geng::State for Game {
             }
         }
 
-        if let Some(results) = &self.results {
+        if let Some(results) = &self.sim.results {
             if self.show_ui {
-                let text = if results.win {
-                    "You WON! WOW"
-                } else {
-                    "You LOST! LUL"
+                let controls = self.ctx.controls.borrow();
+                let versus_config = &self.ctx.assets.config.versus;
+                let text = match &self.versus {
+                    Some(versus) if versus.is_over(versus_config) => versus.winner_text(),
+                    Some(_) if results.win => "Runner wins the round!",
+                    Some(_) => "Snake wins the round!",
+                    None if results.win => "You WON! WOW",
+                    None => "You LOST! LUL",
                 };
                 let mut ui_camera = ui_camera;
                 ui_camera.center.y -= 2.0;
@@ -888,10 +1085,19 @@ impl geng::State for Game {
                     self.ctx.assets.config.outline_size,
                     self.ctx.assets.config.outline_color,
                 );
+                if let Some((leaderboard, placement)) = &self.leaderboard {
+                    self.draw_leaderboard(framebuffer, &ui_camera, leaderboard, placement);
+                }
                 self.ctx.assets.font.draw_with_outline(
                     framebuffer,
                     &ui_camera,
-                    &format!("score: {}", results.score),
+                    &match &self.versus {
+                        Some(versus) => format!(
+                            "runner {} - {} snake",
+                            versus.runner_wins, versus.snake_wins
+                        ),
+                        None => format!("score: {}", results.score),
+                    },
                     vec2::splat(geng::TextAlign::CENTER),
                     mat3::translate(vec2(0.0, -1.5)),
                     Rgba::WHITE,
@@ -908,15 +1114,10 @@ impl geng::State for Game {
                     self.ctx.assets.config.outline_size,
                     self.ctx.assets.config.outline_color,
                 );
-                let millis = (results.time * 1000.0).round() as i64;
-                let secs = millis / 1000;
-                let mins = secs / 60;
-                let secs = secs % 60;
-                let millis = millis % 1000;
                 self.ctx.assets.font.draw_with_outline(
                     framebuffer,
                     &ui_camera,
-                    &format!("{:02}:{:02}.{:03}", mins, secs, millis),
+                    &format_time(results.time),
                     vec2::splat(geng::TextAlign::CENTER),
                     mat3::translate(vec2(0.0, -3.0)) * mat3::scale_uniform(0.5),
                     Rgba::WHITE,
@@ -927,7 +1128,28 @@ impl geng::State for Game {
                 self.ctx.assets.font.draw_with_outline(
                     framebuffer,
                     &ui_camera,
-                    "press R to restart",
+                    &if let Some(versus) = &self.versus {
+                        format!(
+                            "press {} for {}, Escape for menu",
+                            Controls::key_name(&controls.restart),
+                            if versus.is_over(versus_config) {
+                                "a rematch"
+                            } else {
+                                "next round"
+                            },
+                        )
+                    } else if results.win && self.next_level().is_some() {
+                        format!(
+                            "press {} for next level, {} to restart",
+                            Controls::key_name(&controls.next_level),
+                            Controls::key_name(&controls.restart),
+                        )
+                    } else {
+                        format!(
+                            "press {} to restart, Escape for menu",
+                            Controls::key_name(&controls.restart),
+                        )
+                    },
                     vec2::splat(geng::TextAlign::CENTER),
                     mat3::translate(vec2(0.0, -4.0)) * mat3::scale_uniform(0.5),
                     Rgba::WHITE,
@@ -937,25 +1159,58 @@ impl geng::State for Game {
                 self.ctx.assets.font.draw_with_outline(
                     framebuffer,
                     &ui_camera,
-                    "press Tab to hide this screen",
+                    &format!(
+                        "press {} to hide this screen",
+                        Controls::key_name(&controls.toggle_ui),
+                    ),
                     vec2::splat(geng::TextAlign::CENTER),
                     mat3::translate(vec2(0.0, -5.0)) * mat3::scale_uniform(0.5),
                     Rgba::WHITE,
                     self.ctx.assets.config.outline_size,
                     self.ctx.assets.config.outline_color,
                 );
+                self.ctx.assets.font.draw_with_outline(
+                    framebuffer,
+                    &ui_camera,
+                    &format!("seed: {}, {} difficulty", self.sim.seed, self.difficulty),
+                    vec2::splat(geng::TextAlign::CENTER),
+                    mat3::translate(vec2(0.0, -6.0)) * mat3::scale_uniform(0.5),
+                    Rgba::WHITE,
+                    self.ctx.assets.config.outline_size,
+                    self.ctx.assets.config.outline_color,
+                );
             }
         } else {
-            let millis = (self.time * 1000.0).round() as i64;
-            let secs = millis / 1000;
-            let mins = secs / 60;
-            let secs = secs % 60;
-            let millis = millis % 1000;
             let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            let goal_text = match (&self.versus, &self.sim.map.header.goal) {
+                (Some(_), _) => Some(format!(
+                    "survive {}",
+                    format_time(
+                        (self.ctx.assets.config.versus.round_time - self.sim.time).max(0.0)
+                    ),
+                )),
+                (None, Some(goal)) => Some(format!("goal: {goal}")),
+                (None, None) => None,
+            };
+            if let Some(goal_text) = goal_text {
+                self.ctx.assets.font.draw(
+                    framebuffer,
+                    &ui_camera,
+                    &goal_text,
+                    vec2(geng::TextAlign::CENTER, geng::TextAlign::TOP),
+                    mat3::translate(
+                        ui_camera.screen_to_world(
+                            framebuffer_size,
+                            vec2(framebuffer_size.x / 2.0, framebuffer_size.y),
+                        ) + vec2(0.0, -0.5),
+                    ) * mat3::scale_uniform(0.5),
+                    Rgba::WHITE,
+                );
+            }
             self.ctx.assets.font.draw(
                 framebuffer,
                 &ui_camera,
-                &format!("{:02}:{:02}.{:03}", mins, secs, millis),
+                &format_time(self.sim.time),
                 vec2(geng::TextAlign::RIGHT, geng::TextAlign::TOP),
                 mat3::translate(
                     ui_camera.screen_to_world(framebuffer_size, framebuffer_size) - vec2(0.5, 0.5),
@@ -965,7 +1220,26 @@ impl geng::State for Game {
             self.ctx.assets.font.draw(
                 framebuffer,
                 &ui_camera,
-                &format!("score: {}", self.score),
+                &if let Some(versus) = &self.versus {
+                    format!(
+                        "round {}/{}: runner {} - {} snake",
+                        versus.rounds_played() + 1,
+                        self.ctx.assets.config.versus.rounds,
+                        versus.runner_wins,
+                        versus.snake_wins,
+                    )
+                } else if self.sim.players.len() > 1 {
+                    let scores: Vec<String> = self
+                        .sim
+                        .players
+                        .iter()
+                        .enumerate()
+                        .map(|(index, player)| format!("P{}: {}", index + 1, player.score))
+                        .collect();
+                    format!("score: {} ({})", self.sim.score, scores.join(", "))
+                } else {
+                    format!("score: {}", self.sim.score)
+                },
                 vec2(geng::TextAlign::LEFT, geng::TextAlign::TOP),
                 mat3::translate(
                     ui_camera.screen_to_world(framebuffer_size, vec2(0.0, framebuffer_size.y))
@@ -973,11 +1247,16 @@ impl geng::State for Game {
                 ) * mat3::scale_uniform(0.5),
                 Rgba::WHITE,
             );
-            if !self.player_moved {
+            if !self.sim.player_moved {
                 self.ctx.assets.font.draw_with_outline(
                     framebuffer,
                     &ui_camera,
-                    &"Use WASD/Arrows to move",
+                    &match &self.remote {
+                        Some(remote) if remote.missing_players > 0 => {
+                            format!("Waiting for {} more players", remote.missing_players)
+                        }
+                        _ => "Use WASD/Arrows to move".to_owned(),
+                    },
                     vec2::splat(geng::TextAlign::CENTER),
                     mat3::scale_uniform(0.5),
                     Rgba::WHITE,
@@ -986,5 +1265,22 @@ impl geng::State for Game {
                 );
             }
         }
+        if let Some((text, _)) = &self.notice {
+            let framebuffer_size = framebuffer.size().map(|x| x as f32);
+            self.ctx.assets.font.draw_with_outline(
+                framebuffer,
+                &ui_camera,
+                text,
+                vec2(geng::TextAlign::CENTER, geng::TextAlign::BOTTOM),
+                mat3::translate(
+                    ui_camera
+                        .screen_to_world(framebuffer_size, vec2(framebuffer_size.x / 2.0, 0.0))
+                        + vec2(0.0, 0.5),
+                ) * mat3::scale_uniform(0.5),
+                Rgba::WHITE,
+                self.ctx.assets.config.outline_size,
+                self.ctx.assets.config.outline_color,
+            );
+        }
     }
 }
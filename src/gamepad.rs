@@ -0,0 +1,139 @@
+use super::*;
+
+/// Gamepad buttons for each action, see [Controls]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GamepadControls {
+    pub use_item: Vec<gilrs::Button>,
+    pub left: Vec<gilrs::Button>,
+    pub right: Vec<gilrs::Button>,
+    pub up: Vec<gilrs::Button>,
+    pub down: Vec<gilrs::Button>,
+    pub restart: Vec<gilrs::Button>,
+    pub next_level: Vec<gilrs::Button>,
+    pub pause: Vec<gilrs::Button>,
+    /// Left stick tilt below which it is considered centered
+    pub stick_dead_zone: f32,
+}
+
+/// Connected gamepads, turned into the same key events the keyboard produces
+pub struct Gamepads {
+    /// `None` if gamepads are not supported on this platform
+    gilrs: Option<gilrs::Gilrs>,
+    /// Direction the left stick pointed at on the last poll
+    stick_dir: Option<vec2<isize>>,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepads are not available: {e}");
+                None
+            }
+        };
+        Self {
+            gilrs,
+            stick_dir: None,
+        }
+    }
+
+    /// Key presses for the gamepad actions since the last poll.
+    ///
+    /// Each action presses the first key bound to it, pause presses Escape.
+    pub fn poll(&mut self, controls: &Controls) -> Vec<geng::Event> {
+        let Some(gilrs) = &mut self.gilrs else {
+            return Vec::new();
+        };
+        let buttons = &controls.gamepad;
+        let mut keys = Vec::new();
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            let gilrs::EventType::ButtonPressed(button, _) = event else {
+                continue;
+            };
+            for (bound, key) in [
+                (&buttons.use_item, controls.use_item.first()),
+                (&buttons.left, controls.left.first()),
+                (&buttons.right, controls.right.first()),
+                (&buttons.up, controls.up.first()),
+                (&buttons.down, controls.down.first()),
+                (&buttons.restart, controls.restart.first()),
+                (&buttons.next_level, controls.next_level.first()),
+                (&buttons.pause, Some(&geng::Key::Escape)),
+            ] {
+                if let Some(&key) = key.filter(|_| bound.contains(&button)) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        // Tilting the stick into a new direction works like a d-pad press
+        let stick_dir = self.stick_dir(controls);
+        if stick_dir != self.stick_dir {
+            self.stick_dir = stick_dir;
+            let key = match stick_dir {
+                Some(dir) if dir == vec2(-1, 0) => controls.left.first(),
+                Some(dir) if dir == vec2(1, 0) => controls.right.first(),
+                Some(dir) if dir == vec2(0, 1) => controls.up.first(),
+                Some(_) => controls.down.first(),
+                None => None,
+            };
+            keys.extend(key.copied());
+        }
+        keys.into_iter()
+            .map(|key| geng::Event::KeyPress { key })
+            .collect()
+    }
+
+    /// Direction of the left stick of any gamepad, if tilted past the dead zone
+    fn stick_dir(&self, controls: &Controls) -> Option<vec2<isize>> {
+        let gilrs = self.gilrs.as_ref()?;
+        gilrs.gamepads().find_map(|(_, gamepad)| {
+            let stick = vec2(
+                gamepad.value(gilrs::Axis::LeftStickX),
+                gamepad.value(gilrs::Axis::LeftStickY),
+            );
+            if stick.len() < controls.gamepad.stick_dead_zone {
+                None
+            } else if stick.x.abs() > stick.y.abs() {
+                Some(vec2(stick.x.signum() as isize, 0))
+            } else {
+                Some(vec2(0, stick.y.signum() as isize))
+            }
+        })
+    }
+
+    /// Directions held on any gamepad's d-pad or left stick
+    pub fn held_dirs(&self, controls: &Controls) -> Vec<vec2<isize>> {
+        let Some(gilrs) = &self.gilrs else {
+            return Vec::new();
+        };
+        let buttons = &controls.gamepad;
+        let mut dirs = Vec::new();
+        for (bound, dir) in [
+            (&buttons.left, vec2(-1, 0)),
+            (&buttons.right, vec2(1, 0)),
+            (&buttons.up, vec2(0, 1)),
+            (&buttons.down, vec2(0, -1)),
+        ] {
+            let held = gilrs
+                .gamepads()
+                .any(|(_, gamepad)| bound.iter().any(|&button| gamepad.is_pressed(button)));
+            if held {
+                dirs.push(dir);
+            }
+        }
+        if let Some(dir) = self.stick_dir(controls) {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+}
@@ -0,0 +1,93 @@
+use super::*;
+
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Record {
+    pub score: usize,
+    pub time: f64,
+    pub seed: u64,
+}
+
+/// Best runs on a single map
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Leaderboard {
+    pub by_score: Vec<Record>,
+    pub by_time: Vec<Record>,
+}
+
+/// Places a submitted run took in the leaderboard, if any
+#[derive(Default)]
+pub struct Placement {
+    pub by_score: Option<usize>,
+    pub by_time: Option<usize>,
+}
+
+impl Placement {
+    pub fn is_record(&self) -> bool {
+        self.by_score == Some(0) || self.by_time == Some(0)
+    }
+}
+
+fn insert(
+    list: &mut Vec<Record>,
+    record: Record,
+    max_entries: usize,
+    is_better_or_equal: impl Fn(&Record) -> bool,
+) -> Option<usize> {
+    // Ties go to the older record
+    let place = list.partition_point(is_better_or_equal);
+    if place >= max_entries {
+        return None;
+    }
+    list.insert(place, record);
+    list.truncate(max_entries);
+    Some(place)
+}
+
+impl Leaderboard {
+    pub fn submit(&mut self, record: Record, max_entries: usize) -> Placement {
+        Placement {
+            by_score: insert(&mut self.by_score, record.clone(), max_entries, |other| {
+                other.score >= record.score
+            }),
+            by_time: insert(&mut self.by_time, record.clone(), max_entries, |other| {
+                other.time >= record.time
+            }),
+        }
+    }
+}
+
+/// Leaderboards of all maps and difficulties, persisted between runs
+#[derive(Serialize, Deserialize, Default)]
+pub struct Highscores {
+    maps: BTreeMap<String, BTreeMap<Difficulty, Leaderboard>>,
+}
+
+impl Highscores {
+    const KEY: &'static str = "highscores";
+
+    pub fn load() -> Self {
+        preferences::load(Self::KEY).unwrap_or_default()
+    }
+
+    /// Add a run to the leaderboard of the map on given difficulty and save it
+    pub fn submit(
+        map: &str,
+        difficulty: Difficulty,
+        record: Record,
+        max_entries: usize,
+    ) -> (Leaderboard, Placement) {
+        let mut highscores = Self::load();
+        let leaderboard = highscores
+            .maps
+            .entry(map.to_owned())
+            .or_default()
+            .entry(difficulty)
+            .or_default();
+        let placement = leaderboard.submit(record, max_entries);
+        let leaderboard = leaderboard.clone();
+        preferences::save(Self::KEY, &highscores);
+        (leaderboard, placement)
+    }
+}
@@ -1,8 +1,9 @@
 use super::*;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Id(u64);
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct IdGen {
     next_id: u64,
 }
@@ -0,0 +1,189 @@
+use super::*;
+
+struct Entry {
+    /// Campaign level index, `None` for the endless mode
+    level: Option<usize>,
+    name: String,
+    goal: Option<Goal>,
+}
+
+pub struct LevelSelect {
+    ctx: Context,
+    progress: campaign::Progress,
+    difficulty: Difficulty,
+    /// Roles of the local players the game is started for
+    roles: Vec<Role>,
+    entries: Vec<Entry>,
+    selected: usize,
+    transition: Option<geng::state::Transition>,
+}
+
+impl LevelSelect {
+    pub fn new(ctx: &Context, roles: &[Role]) -> Self {
+        let mut entries = vec![Entry {
+            level: None,
+            name: "Endless".to_owned(),
+            goal: None,
+        }];
+        for (index, level) in ctx.levels.iter().enumerate() {
+            let header = Map::parse(&level.map).ok().map(|map| map.header);
+            entries.push(Entry {
+                level: Some(index),
+                name: header
+                    .as_ref()
+                    .and_then(|header| header.name.clone())
+                    .unwrap_or_else(|| level.file.clone()),
+                goal: header.and_then(|header| header.goal),
+            });
+        }
+        let progress = campaign::Progress::load();
+        // Endless mode goes first, then the first level that is not completed yet
+        let selected = (progress.completed + 1).min(entries.len() - 1);
+        Self {
+            ctx: ctx.clone(),
+            progress,
+            difficulty: Difficulty::load(),
+            roles: roles.to_vec(),
+            entries,
+            selected,
+            transition: None,
+        }
+    }
+
+    fn is_unlocked(&self, entry: &Entry) -> bool {
+        entry
+            .level
+            .is_none_or(|level| self.progress.is_unlocked(level))
+    }
+
+    fn start(&mut self) {
+        let entry = &self.entries[self.selected];
+        if !self.is_unlocked(entry) {
+            return;
+        }
+        let game = Game::new(&self.ctx, entry.level, self.difficulty, &self.roles);
+        let state: Box<dyn geng::State> = match game {
+            Ok(game) => Box::new(game),
+            Err(e) => Box::new(ErrorScreen::new(&self.ctx, e)),
+        };
+        self.transition = Some(geng::state::Transition::Switch(state));
+    }
+}
+
+impl geng::State for LevelSelect {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        let controls = self.ctx.controls.borrow().clone();
+        match event {
+            geng::Event::KeyPress { key } if controls.up.contains(&key) => {
+                self.selected = (self.selected + self.entries.len() - 1) % self.entries.len();
+            }
+            geng::Event::KeyPress { key } if controls.down.contains(&key) => {
+                self.selected = (self.selected + 1) % self.entries.len();
+            }
+            geng::Event::KeyPress { key } if controls.left.contains(&key) => {
+                self.difficulty = self.difficulty.cycle(-1);
+                self.difficulty.save();
+            }
+            geng::Event::KeyPress { key } if controls.right.contains(&key) => {
+                self.difficulty = self.difficulty.cycle(1);
+                self.difficulty.save();
+            }
+            geng::Event::KeyPress { key } if controls.use_item.contains(&key) => {
+                self.start();
+            }
+            geng::Event::KeyPress {
+                key: geng::Key::Escape,
+            } => {
+                self.transition = Some(geng::state::Transition::Switch(Box::new(Title::new(
+                    &self.ctx,
+                ))));
+            }
+            geng::Event::KeyPress { key } if controls.fullscreen.contains(&key) => {
+                self.ctx.geng.window().toggle_fullscreen();
+            }
+            _ => {}
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let config = &self.ctx.assets.config;
+        ugli::clear(framebuffer, Some(config.colors.background), None, None);
+        let camera = geng::Camera2d {
+            center: vec2::ZERO,
+            rotation: Angle::ZERO,
+            fov: config.ui_fov,
+        };
+        self.ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            &if self.roles.contains(&Role::Snake) {
+                "Select level (versus)".to_owned()
+            } else if self.roles.len() > 1 {
+                format!("Select level ({} players)", self.roles.len())
+            } else {
+                "Select level".to_owned()
+            },
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, 3.5)),
+            Rgba::WHITE,
+            config.outline_size,
+            config.outline_color,
+        );
+        self.ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            &format!("< difficulty: {} >", self.difficulty),
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, 2.75)) * mat3::scale_uniform(0.4),
+            config.colors.player,
+            config.outline_size,
+            config.outline_color,
+        );
+        for (i, entry) in self.entries.iter().enumerate() {
+            let mut text = entry.name.clone();
+            if !self.is_unlocked(entry) {
+                text += " (locked)";
+            } else if let Some(goal) = &entry.goal {
+                text += &format!(" - {goal}");
+            }
+            let color = if i == self.selected {
+                config.colors.player
+            } else if self.is_unlocked(entry) {
+                Rgba::WHITE
+            } else {
+                config.colors.wall
+            };
+            self.ctx.assets.font.draw_with_outline(
+                framebuffer,
+                &camera,
+                &text,
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(vec2(0.0, 2.0 - i as f32 * 0.75)) * mat3::scale_uniform(0.5),
+                color,
+                config.outline_size,
+                config.outline_color,
+            );
+        }
+        let hint = format!(
+            "Up/Down to select, Left/Right for difficulty, {} to start",
+            Controls::key_name(&self.ctx.controls.borrow().use_item)
+        );
+        self.ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            &hint,
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, -4.0)) * mat3::scale_uniform(0.3),
+            Rgba::WHITE,
+            config.outline_size,
+            config.outline_color,
+        );
+    }
+}
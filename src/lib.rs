@@ -0,0 +1,509 @@
+use geng::prelude::*;
+
+mod brain;
+mod campaign;
+mod controls_menu;
+mod difficulty;
+mod error_screen;
+mod game;
+mod gamepad;
+mod highscores;
+mod id;
+mod level_select;
+mod map;
+mod menu;
+mod net;
+mod options;
+mod pause;
+mod replay;
+mod save;
+pub mod server;
+mod simulation;
+mod snake;
+mod title;
+
+use brain::{BrainKind, BrainState, SnakeBrain};
+use campaign::{Campaign, Level};
+use difficulty::Difficulty;
+use error_screen::ErrorScreen;
+use game::*;
+use id::*;
+use level_select::LevelSelect;
+use map::*;
+use replay::Replay;
+use save::SaveGame;
+use simulation::{Role, Simulation};
+use title::Title;
+
+#[derive(Deserialize, Clone)]
+pub struct Weights {
+    pub food: f64,
+    pub reverse: f64,
+    pub snake_speed_up: f64,
+    pub snake_speed_down: f64,
+    pub snake_split: f64,
+    // Newer items are zero when left out, so older weight tables stay valid
+    #[serde(default)]
+    pub freeze: f64,
+    #[serde(default)]
+    pub wall: f64,
+    #[serde(default)]
+    pub decoy: f64,
+    #[serde(default)]
+    pub dash: f64,
+    #[serde(default)]
+    pub teleport: f64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Colors {
+    pub background: Rgba<f32>,
+    pub wall: Rgba<f32>,
+    pub player: Rgba<f32>,
+    pub food: Rgba<f32>,
+    pub snake_head: Rgba<f32>,
+    pub snake_tail: Rgba<f32>,
+    pub snake: Vec<Rgba<f32>>,
+    /// Tints of local players, in order
+    pub player_tints: Vec<Rgba<f32>>,
+    pub hovered: Rgba<f32>,
+    pub reverse: Rgba<f32>,
+    pub snake_vision: Rgba<f32>,
+    pub snake_speed_up: Rgba<f32>,
+    pub snake_speed_down: Rgba<f32>,
+    pub snake_split: Rgba<f32>,
+    /// Item color, also used for frozen snakes
+    pub freeze: Rgba<f32>,
+    /// Item color, also used for walls placed with it
+    pub wall_item: Rgba<f32>,
+    /// Tint of the decoys, which look like players
+    pub decoy: Rgba<f32>,
+    pub dash: Rgba<f32>,
+    pub teleport: Rgba<f32>,
+    /// Portal colors by portal number, cycled through
+    pub portals: Vec<Rgba<f32>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SnakeSpeedItemConfig {
+    pub multiplier: f64,
+    pub time: f64,
+}
+
+/// Item that lasts for a while
+#[derive(Deserialize, Clone)]
+pub struct TimedItemConfig {
+    pub time: f64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DashItemConfig {
+    /// Most cells crossed at once
+    pub cells: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TeleportItemConfig {
+    /// Distance to the closest snake head the player lands at, if the map has room for it
+    pub min_distance: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ItemsConfig {
+    pub snake_speed: SnakeSpeedItemConfig,
+    /// Seconds the snake stays still
+    pub freeze: TimedItemConfig,
+    /// Seconds until the placed wall crumbles
+    pub wall: TimedItemConfig,
+    /// Seconds the decoy stays if not eaten
+    pub decoy: TimedItemConfig,
+    pub dash: DashItemConfig,
+    pub teleport: TeleportItemConfig,
+}
+
+/// Rules of a versus match, where a second player steers a snake
+#[derive(Deserialize, Clone)]
+pub struct VersusConfig {
+    /// Most rounds in a match, it ends early once a side won more than half of them
+    pub rounds: usize,
+    /// Seconds the runner has to survive to win a round
+    pub round_time: f64,
+}
+
+/// Keys of the actions each local player has
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PlayerControls {
+    pub use_item: Vec<geng::Key>,
+    pub left: Vec<geng::Key>,
+    pub right: Vec<geng::Key>,
+    pub up: Vec<geng::Key>,
+    pub down: Vec<geng::Key>,
+}
+
+impl PlayerControls {
+    /// Movement keys with their directions
+    pub fn dirs(&self) -> [(&Vec<geng::Key>, vec2<isize>); 4] {
+        [
+            (&self.left, vec2(-1, 0)),
+            (&self.right, vec2(1, 0)),
+            (&self.up, vec2(0, 1)),
+            (&self.down, vec2(0, -1)),
+        ]
+    }
+
+    pub fn contains(&self, key: geng::Key) -> bool {
+        self.use_item.contains(&key) || self.dirs().iter().any(|(keys, _)| keys.contains(&key))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Controls {
+    pub use_item: Vec<geng::Key>,
+    pub left: Vec<geng::Key>,
+    pub right: Vec<geng::Key>,
+    pub up: Vec<geng::Key>,
+    pub down: Vec<geng::Key>,
+    pub restart: Vec<geng::Key>,
+    pub next_level: Vec<geng::Key>,
+    pub toggle_music: Vec<geng::Key>,
+    pub toggle_ui: Vec<geng::Key>,
+    pub fullscreen: Vec<geng::Key>,
+    // Actions added after controls could be saved in preferences,
+    // missing from older ones and taken from the shipped config then
+    #[serde(default = "Controls::shipped_quicksave")]
+    pub quicksave: Vec<geng::Key>,
+    #[serde(default = "Controls::shipped_quickload")]
+    pub quickload: Vec<geng::Key>,
+    #[serde(default = "Controls::shipped_gamepad")]
+    pub gamepad: gamepad::GamepadControls,
+    /// Controls of the second and further players in co-op
+    #[serde(default = "Controls::shipped_coop")]
+    pub coop: Vec<PlayerControls>,
+}
+
+impl Controls {
+    /// Controls from `assets/config.toml` as it comes with the game
+    fn shipped() -> Self {
+        #[derive(Deserialize)]
+        struct ShippedConfig {
+            controls: Controls,
+        }
+        let config: ShippedConfig = toml::from_str(include_str!("../assets/config.toml"))
+            .expect("Shipped config has invalid controls");
+        config.controls
+    }
+    fn shipped_quicksave() -> Vec<geng::Key> {
+        Self::shipped().quicksave
+    }
+    fn shipped_quickload() -> Vec<geng::Key> {
+        Self::shipped().quickload
+    }
+    fn shipped_gamepad() -> gamepad::GamepadControls {
+        Self::shipped().gamepad
+    }
+    fn shipped_coop() -> Vec<PlayerControls> {
+        Self::shipped().coop
+    }
+
+    /// Every action with its display name, in the order shown in the controls menu
+    pub fn actions_mut(&mut self) -> Vec<(String, &mut Vec<geng::Key>)> {
+        let mut actions: Vec<(String, &mut Vec<geng::Key>)> = vec![
+            ("use item".to_owned(), &mut self.use_item),
+            ("left".to_owned(), &mut self.left),
+            ("right".to_owned(), &mut self.right),
+            ("up".to_owned(), &mut self.up),
+            ("down".to_owned(), &mut self.down),
+            ("restart".to_owned(), &mut self.restart),
+            ("next level".to_owned(), &mut self.next_level),
+            ("toggle music".to_owned(), &mut self.toggle_music),
+            ("toggle end screen".to_owned(), &mut self.toggle_ui),
+            ("fullscreen".to_owned(), &mut self.fullscreen),
+            ("quicksave".to_owned(), &mut self.quicksave),
+            ("quickload".to_owned(), &mut self.quickload),
+        ];
+        for (i, player) in self.coop.iter_mut().enumerate() {
+            let prefix = format!("P{}", i + 2);
+            actions.extend([
+                (format!("{prefix} use item"), &mut player.use_item),
+                (format!("{prefix} left"), &mut player.left),
+                (format!("{prefix} right"), &mut player.right),
+                (format!("{prefix} up"), &mut player.up),
+                (format!("{prefix} down"), &mut player.down),
+            ]);
+        }
+        actions
+    }
+
+    /// Most local players that can play at once
+    pub fn max_players(&self) -> usize {
+        1 + self.coop.len()
+    }
+
+    /// Controls of the `index`th player, the first one uses the main controls.
+    /// Players of a replay or save with more of them than set up here get no keys
+    pub fn player(&self, index: usize) -> PlayerControls {
+        match index {
+            0 => PlayerControls {
+                use_item: self.use_item.clone(),
+                left: self.left.clone(),
+                right: self.right.clone(),
+                up: self.up.clone(),
+                down: self.down.clone(),
+            },
+            _ => self.coop.get(index - 1).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Name of the first key bound to an action, for hints
+    pub fn key_name(keys: &[geng::Key]) -> String {
+        match keys.first() {
+            Some(key) => format!("{key:?}"),
+            None => "<unbound>".to_owned(),
+        }
+    }
+}
+
+#[derive(geng::asset::Load, Deserialize, Clone)]
+#[load(serde = "toml")]
+pub struct Config {
+    pub outline_size: f32,
+    pub outline_color: Rgba<f32>,
+    pub particle_opacity: f32,
+    pub particle_lifetime: f64,
+    pub particle_amount: usize,
+    pub particle_size: f32,
+    pub particle_max_speed: f32,
+    pub ui_fov: f32,
+    pub start_snake_size: usize,
+    pub items: ItemsConfig,
+    pub snake_speed: f64,
+    pub player_speed: f64,
+    /// How many quick presses are remembered for the next player steps
+    pub move_queue_size: usize,
+    pub new_item_time: f64,
+    pub cell_margin: f32,
+    pub camera_margin: f32,
+    pub snake_vision: usize,
+    /// Brains given to snakes in spawn order, cycling when there are more snakes
+    pub snake_brains: Vec<BrainKind>,
+    pub versus: VersusConfig,
+    pub colors: Colors,
+    pub controls: Controls,
+    pub weights: Weights,
+    pub food_value: usize,
+    pub time_scale: f64,
+    pub ticks_per_second: f64,
+    pub max_items: usize,
+    pub snake_wake_up_time: f64,
+    pub snake_reverse_speed: f64,
+    pub volume: f64,
+    pub music_volume: f64,
+    /// Number of runs kept in each leaderboard
+    pub highscore_entries: usize,
+    /// Overrides applied for each difficulty, after the map's own
+    pub difficulties: std::collections::BTreeMap<Difficulty, ConfigOverrides>,
+}
+
+impl Config {
+    /// Config to play `header`'s map with on given difficulty
+    pub fn for_map(&self, header: &MapHeader, difficulty: Difficulty) -> Self {
+        let mut config = self.clone();
+        header.config.apply(&mut config);
+        difficulty.apply(&mut config);
+        config
+    }
+}
+
+/// Partial [Config] overriding gameplay values
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigOverrides {
+    pub snake_speed: Option<f64>,
+    pub player_speed: Option<f64>,
+    pub new_item_time: Option<f64>,
+    pub snake_vision: Option<usize>,
+    pub snake_brains: Option<Vec<BrainKind>>,
+    pub start_snake_size: Option<usize>,
+    pub food_value: Option<usize>,
+    pub max_items: Option<usize>,
+    pub snake_wake_up_time: Option<f64>,
+    pub snake_reverse_speed: Option<f64>,
+    pub weights: Option<Weights>,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut Config) {
+        macro_rules! apply {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(value) = &self.$field {
+                        config.$field = value.clone();
+                    }
+                )*
+            };
+        }
+        apply!(
+            snake_speed,
+            player_speed,
+            new_item_time,
+            snake_vision,
+            snake_brains,
+            start_snake_size,
+            food_value,
+            max_items,
+            snake_wake_up_time,
+            snake_reverse_speed,
+            weights
+        );
+    }
+}
+
+#[derive(geng::asset::Load)]
+pub struct Sfx {
+    pub eat: geng::Sound,
+    pub ded: geng::Sound,
+    pub end: geng::Sound,
+    pub pickup: geng::Sound,
+    pub start: geng::Sound,
+    pub use_item: geng::Sound,
+}
+
+#[derive(geng::asset::Load)]
+pub struct Textures {
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub snek: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub food: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub player: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub reverse: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub speeddown: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub speedup: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub split: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub freeze: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub wall: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub decoy: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub dash: Rc<ugli::Texture>,
+    #[load(options(filter = "ugli::Filter::Nearest"))]
+    pub teleport: Rc<ugli::Texture>,
+}
+
+#[derive(geng::asset::Load)]
+pub struct Assets {
+    #[load(path = "font/PixeloidSansBold-PKnYd.ttf")]
+    pub font: geng::Font,
+    pub map: String,
+    #[load(path = "levels/campaign.toml")]
+    pub campaign: Campaign,
+    pub config: Config,
+    pub textures: Textures,
+    pub sfx: Sfx,
+    #[load(path = "snake.mp3", options(looped = "true"))]
+    pub music: geng::Sound,
+}
+
+#[derive(Clone)]
+pub struct Context {
+    pub geng: Geng,
+    pub assets: Rc<Assets>,
+    /// Controls from the asset config, unless the player rebound them
+    pub controls: Rc<std::cell::RefCell<Controls>>,
+    pub gamepads: Rc<std::cell::RefCell<gamepad::Gamepads>>,
+    pub cli: Rc<CliArgs>,
+    pub levels: Rc<Vec<Level>>,
+}
+
+impl Context {
+    /// Gamepad presses since the last call, states feed them to their `handle_event`
+    pub fn gamepad_events(&self) -> Vec<geng::Event> {
+        let controls = self.controls.borrow();
+        self.gamepads.borrow_mut().poll(&controls)
+    }
+}
+
+#[derive(clap::Parser)]
+pub struct CliArgs {
+    #[clap(long)]
+    pub editor: bool,
+    /// Seed for all gameplay randomness, random if not specified
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Play back a recorded replay file
+    #[clap(long)]
+    pub replay: Option<std::path::PathBuf>,
+    /// Difficulty to play on, remembered for later runs
+    #[clap(long, value_enum)]
+    pub difficulty: Option<Difficulty>,
+    /// Resume a saved game
+    #[clap(long)]
+    pub load: Option<std::path::PathBuf>,
+    /// Join a game hosted by `snaked-server`, like `192.168.0.2:1155`
+    #[clap(long)]
+    pub connect: Option<String>,
+}
+
+/// Run the game client
+pub fn run() {
+    let cli: CliArgs = cli::parse();
+    Geng::run("Snaked", |geng| async move {
+        let assets: Assets = geng
+            .asset_manager()
+            .load(run_dir().join("assets"))
+            .await
+            .unwrap();
+        let options = options::Options::load(&assets.config);
+        geng.audio().set_volume(options.volume);
+        let mut levels = Vec::new();
+        for file in &assets.campaign.levels {
+            let map: String = geng
+                .asset_manager()
+                .load(run_dir().join("assets").join("levels").join(file))
+                .await
+                .unwrap();
+            levels.push(Level {
+                file: file.clone(),
+                map,
+            });
+        }
+        if let Some(difficulty) = cli.difficulty {
+            difficulty.save();
+        }
+        let controls = options
+            .controls
+            .unwrap_or_else(|| assets.config.controls.clone());
+        let ctx = Context {
+            geng: geng.clone(),
+            controls: Rc::new(std::cell::RefCell::new(controls)),
+            gamepads: default(),
+            assets: Rc::new(assets),
+            cli: Rc::new(cli),
+            levels: Rc::new(levels),
+        };
+        if let Some(addr) = &ctx.cli.connect {
+            match Game::connect(&ctx, addr) {
+                Ok(game) => geng.run_state(game).await,
+                Err(e) => geng.run_state(ErrorScreen::new(&ctx, e)).await,
+            }
+        } else if let Some(path) = &ctx.cli.load {
+            match SaveGame::load(path).and_then(|save| Game::load(&ctx, save)) {
+                Ok(game) => geng.run_state(game).await,
+                Err(e) => geng.run_state(ErrorScreen::new(&ctx, e)).await,
+            }
+        } else if ctx.cli.editor || ctx.cli.replay.is_some() {
+            match Game::new(&ctx, None, Difficulty::load(), &[Role::Runner]) {
+                Ok(game) => geng.run_state(game).await,
+                Err(e) => geng.run_state(ErrorScreen::new(&ctx, e)).await,
+            }
+        } else {
+            geng.run_state(Title::new(&ctx)).await;
+        }
+    });
+}
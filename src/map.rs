@@ -1,17 +1,58 @@
 use super::*;
 
-const DIRECTIONS: [vec2<isize>; 4] = [vec2(-1, 0), vec2(1, 0), vec2(0, -1), vec2(0, 1)];
+use std::collections::{BTreeMap, VecDeque};
 
-#[derive(Clone)]
+/// Unit steps, the only moves there are
+pub const DIRECTIONS: [vec2<isize>; 4] = [vec2(-1, 0), vec2(1, 0), vec2(0, -1), vec2(0, 1)];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum Item {
     Food,
     Reverse,
     SnakeSpeedUp,
     SnakeSpeedDown,
     SnakeSplit,
+    Freeze,
+    Wall,
+    Decoy,
+    Dash,
+    Teleport,
+}
+
+impl Item {
+    /// Character representing a pre-placed item in map files
+    pub fn map_char(&self) -> char {
+        match self {
+            Self::Food => 'f',
+            Self::Reverse => 'r',
+            Self::SnakeSpeedUp => 'u',
+            Self::SnakeSpeedDown => 'd',
+            Self::SnakeSplit => 's',
+            Self::Freeze => 'z',
+            Self::Wall => 'w',
+            Self::Decoy => 'y',
+            Self::Dash => 'a',
+            Self::Teleport => 't',
+        }
+    }
+    pub fn from_map_char(c: char) -> Option<Self> {
+        Some(match c {
+            'f' => Self::Food,
+            'r' => Self::Reverse,
+            'u' => Self::SnakeSpeedUp,
+            'd' => Self::SnakeSpeedDown,
+            's' => Self::SnakeSplit,
+            'z' => Self::Freeze,
+            'w' => Self::Wall,
+            'y' => Self::Decoy,
+            'a' => Self::Dash,
+            't' => Self::Teleport,
+            _ => return None,
+        })
+    }
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MapCell {
     #[default]
     Empty,
@@ -23,38 +64,418 @@ pub enum MapCell {
         snake_id: Id,
         segment_index: u32,
     },
+    /// Stepping into a portal comes out of the other portal with the same number,
+    /// moving on in the same direction
+    Portal(u8),
+    /// Fake player that snakes go for before anything else, see [Item::Decoy]
+    Decoy,
+}
+
+/// Metadata from the map file header
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MapHeader {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// Recommended config overrides for this map
+    #[serde(default)]
+    pub config: ConfigOverrides,
+    /// Win condition, the map is endless without it
+    pub goal: Option<Goal>,
+    #[serde(default)]
+    pub topology: Topology,
+}
+
+/// What happens when stepping off an edge of the map
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Every edge wraps around to the opposite one
+    #[default]
+    Torus,
+    /// Edges block movement
+    Bounded,
+    /// Only the left and right edges wrap around
+    CylinderHorizontal,
+    /// Only the top and bottom edges wrap around
+    CylinderVertical,
+    /// Every edge wraps around, going through the left or right edge also flips upside down
+    KleinBottle,
+}
+
+impl Topology {
+    /// Whether the horizontal and vertical edges wrap around
+    fn wraps(self) -> vec2<bool> {
+        match self {
+            Self::Torus | Self::KleinBottle => vec2(true, true),
+            Self::Bounded => vec2(false, false),
+            Self::CylinderHorizontal => vec2(true, false),
+            Self::CylinderVertical => vec2(false, true),
+        }
+    }
+}
+
+/// All specified conditions have to be met to win
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Goal {
+    /// Seconds to survive
+    pub survive: Option<f64>,
+    pub score: Option<usize>,
+}
+
+impl Goal {
+    pub fn is_reached(&self, time: f64, score: usize) -> bool {
+        (self.survive.is_some() || self.score.is_some())
+            && self.survive.is_none_or(|survive| time >= survive)
+            && self.score.is_none_or(|goal| score >= goal)
+    }
+}
+
+impl std::fmt::Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(survive) = self.survive {
+            parts.push(format!("survive {survive}s"));
+        }
+        if let Some(score) = self.score {
+            parts.push(format!("score {score}"));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[derive(Clone)]
+pub struct SnakeSpawn {
+    /// From tail to head
+    pub body: Vec<vec2<usize>>,
+    pub dir: vec2<isize>,
+}
+
+const HEADER_SEPARATOR: &str = "---";
+const BODY_CHAR: char = 'o';
+const PLAYER_SPAWN_CHAR: char = '@';
+const SNAKE_HEAD_CHARS: [(char, vec2<isize>); 4] = [
+    ('<', vec2(-1, 0)),
+    ('>', vec2(1, 0)),
+    ('v', vec2(0, -1)),
+    ('^', vec2(0, 1)),
+];
+
+#[derive(Debug)]
+pub enum MapError {
+    Empty,
+    Header(toml::de::Error),
+    UnexpectedCharacter {
+        line: usize,
+        column: usize,
+        character: char,
+    },
+    DetachedSnakeBody {
+        line: usize,
+        column: usize,
+    },
+    UnpairedPortal {
+        line: usize,
+        column: usize,
+        number: u8,
+    },
+    NoRoom,
 }
 
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Map is empty"),
+            Self::Header(e) => write!(f, "Invalid map header: {e}"),
+            Self::UnexpectedCharacter {
+                line,
+                column,
+                character,
+            } => write!(
+                f,
+                "Unexpected character {character:?} at line {line}, column {column}"
+            ),
+            Self::DetachedSnakeBody { line, column } => write!(
+                f,
+                "Snake body at line {line}, column {column} is not connected to a head"
+            ),
+            Self::UnpairedPortal {
+                line,
+                column,
+                number,
+            } => write!(
+                f,
+                "Portal {number} at line {line}, column {column} needs exactly one pair"
+            ),
+            Self::NoRoom => write!(f, "Map has no empty cell to spawn the player in"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
 pub struct Map {
+    pub header: MapHeader,
+    /// Header as written in the file, kept so that saving preserves it
+    header_source: String,
+    pub player_spawns: Vec<vec2<usize>>,
+    pub snake_spawns: Vec<SnakeSpawn>,
     cells: Vec<Vec<MapCell>>,
+    /// Segment indices and positions of every snake, ordered from tail to head
+    snakes: BTreeMap<Id, VecDeque<(u32, vec2<usize>)>>,
+    players: BTreeMap<Id, vec2<usize>>,
+    /// Positions of the portals with each number
+    portals: BTreeMap<u8, Vec<vec2<usize>>>,
+    /// Seconds left until each temporary cell turns back into [MapCell::Empty]
+    timers: HashMap<vec2<usize>, f64>,
+    /// In placement order
+    decoys: Vec<vec2<usize>>,
 }
 
 impl Map {
+    fn from_cells(cells: Vec<Vec<MapCell>>) -> Self {
+        let mut map = Self {
+            header: default(),
+            header_source: String::new(),
+            player_spawns: Vec::new(),
+            snake_spawns: Vec::new(),
+            cells,
+            snakes: default(),
+            players: default(),
+            portals: default(),
+            timers: default(),
+            decoys: Vec::new(),
+        };
+        for x in 0..map.size().x {
+            for y in 0..map.size().y {
+                map.index_cell(vec2(x, y));
+            }
+        }
+        map
+    }
+
+    fn index_cell(&mut self, pos: vec2<usize>) {
+        match self.cells[pos.x][pos.y] {
+            MapCell::SnakePart {
+                snake_id,
+                segment_index,
+            } => {
+                let body = self.snakes.entry(snake_id).or_default();
+                let i = body.partition_point(|&(idx, _)| idx < segment_index);
+                body.insert(i, (segment_index, pos));
+            }
+            MapCell::Player(id) => {
+                self.players.insert(id, pos);
+            }
+            MapCell::Portal(number) => {
+                self.portals.entry(number).or_default().push(pos);
+            }
+            MapCell::Decoy => self.decoys.push(pos),
+            _ => {}
+        }
+    }
+
+    fn unindex_cell(&mut self, pos: vec2<usize>) {
+        match self.cells[pos.x][pos.y] {
+            MapCell::SnakePart { snake_id, .. } => {
+                let body = self.snakes.get_mut(&snake_id).unwrap();
+                let i = body.iter().position(|&(_, p)| p == pos).unwrap();
+                body.remove(i);
+                if body.is_empty() {
+                    self.snakes.remove(&snake_id);
+                }
+            }
+            MapCell::Player(id) => {
+                self.players.remove(&id);
+            }
+            MapCell::Portal(number) => {
+                let portals = self.portals.get_mut(&number).unwrap();
+                portals.retain(|&p| p != pos);
+                if portals.is_empty() {
+                    self.portals.remove(&number);
+                }
+            }
+            MapCell::Decoy => self.decoys.retain(|&p| p != pos),
+            _ => {}
+        }
+    }
+
+    /// Replace the cell at `pos`, returning the previous one
+    pub fn set(&mut self, pos: vec2<usize>, cell: MapCell) -> MapCell {
+        self.timers.remove(&pos);
+        self.unindex_cell(pos);
+        let prev = mem::replace(&mut self.cells[pos.x][pos.y], cell);
+        self.index_cell(pos);
+        prev
+    }
+
+    /// Place a cell that is emptied after `time` seconds, unless replaced before that
+    pub fn set_timed(&mut self, pos: vec2<usize>, cell: MapCell, time: f64) -> MapCell {
+        let prev = self.set(pos, cell);
+        self.timers.insert(pos, time);
+        prev
+    }
+
+    /// Seconds left until the cell at `pos` is emptied, if it is temporary
+    pub fn time_left(&self, pos: vec2<usize>) -> Option<f64> {
+        self.timers.get(&pos).copied()
+    }
+
+    /// Temporary cells with their time left, in [Self::iter] order
+    pub fn timers(&self) -> Vec<(vec2<usize>, f64)> {
+        let mut timers: Vec<(vec2<usize>, f64)> = self
+            .timers
+            .iter()
+            .map(|(&pos, &time_left)| (pos, time_left))
+            .collect();
+        timers.sort_by_key(|&(pos, _)| (pos.x, pos.y));
+        timers
+    }
+
+    /// Count down the temporary cells, emptying the expired ones
+    pub fn update_timers(&mut self, delta_time: f64) {
+        let mut expired = Vec::new();
+        for (&pos, time_left) in &mut self.timers {
+            *time_left -= delta_time;
+            if *time_left < 0.0 {
+                expired.push(pos);
+            }
+        }
+        for pos in expired {
+            self.set(pos, MapCell::Empty);
+        }
+    }
+
+    /// Ids of all snakes on the map, in ascending order
+    pub fn snake_ids(&self) -> impl Iterator<Item = Id> + '_ {
+        self.snakes.keys().copied()
+    }
+
+    /// Positions of a snake's segments, from tail to head
+    pub fn snake_body(&self, id: Id) -> impl Iterator<Item = vec2<usize>> + '_ {
+        self.snakes
+            .get(&id)
+            .into_iter()
+            .flat_map(|body| body.iter().map(|&(_, pos)| pos))
+    }
+
+    pub fn snake_head(&self, id: Id) -> vec2<usize> {
+        self.snakes[&id].back().unwrap().1
+    }
+
+    pub fn snake_tail(&self, id: Id) -> vec2<usize> {
+        self.snakes[&id].front().unwrap().1
+    }
+
+    /// Positions of all players on the map, in ascending id order
+    pub fn player_positions(&self) -> impl Iterator<Item = vec2<usize>> + '_ {
+        self.players.values().copied()
+    }
+
+    pub fn player_pos(&self, id: Id) -> Option<vec2<usize>> {
+        self.players.get(&id).copied()
+    }
+
+    pub fn decoy_positions(&self) -> impl Iterator<Item = vec2<usize>> + '_ {
+        self.decoys.iter().copied()
+    }
+
+    /// Shortest offset from `b` to `a` around the wrapping map edges, ignoring portals
     pub fn diff(&self, a: vec2<usize>, b: vec2<usize>) -> vec2<isize> {
-        a.zip(b).zip(self.size()).map(|((a, b), size)| {
-            let a = a as isize;
-            let b = b as isize;
-            let size = size as isize;
-            (-1..=1)
-                .map(|x| a - b + size * x)
-                .min_by_key(|value| value.abs())
-                .unwrap()
-        })
+        let size = self.size().map(|x| x as isize);
+        let a = a.map(|x| x as isize);
+        let b = b.map(|x| x as isize);
+        let wraps = self.header.topology.wraps();
+        let shifts = |wraps: bool| if wraps { -1..=1 } else { 0..=0 };
+        shifts(wraps.x)
+            .flat_map(|x| shifts(wraps.y).map(move |y| vec2(x, y)))
+            .map(|shift| {
+                let a_y = if shift.x != 0 && self.header.topology == Topology::KleinBottle {
+                    size.y - 1 - a.y
+                } else {
+                    a.y
+                };
+                vec2(a.x + shift.x * size.x - b.x, a_y + shift.y * size.y - b.y)
+            })
+            .min_by_key(|diff| diff.x.abs() + diff.y.abs())
+            .unwrap()
     }
-    pub fn distance(&self, a: vec2<usize>, b: vec2<usize>) -> usize {
+    fn wrapped_distance(&self, a: vec2<usize>, b: vec2<usize>) -> usize {
         let diff = self.diff(a, b);
         (diff.x.abs() + diff.y.abs()) as usize
     }
+    /// Number of steps between the cells ignoring walls, going through at most one portal
+    pub fn distance(&self, a: vec2<usize>, b: vec2<usize>) -> usize {
+        // The step into a portal already lands next to its pair
+        self.portal_pairs()
+            .map(|(entry, exit)| {
+                (self.wrapped_distance(a, entry) + self.wrapped_distance(exit, b)).saturating_sub(1)
+            })
+            .fold(self.wrapped_distance(a, b), usize::min)
+    }
+    /// Cells one step away, fewer than four next to edges that do not wrap
     pub fn neighbors(&self, pos: vec2<usize>) -> impl Iterator<Item = vec2<usize>> + '_ {
         DIRECTIONS
             .into_iter()
             .map(move |dir| self.add_dir(pos, dir))
+            .filter(move |&next| next != pos)
+    }
+    /// Direction leading from `from` to its neighbor `to`
+    pub fn dir_to(&self, from: vec2<usize>, to: vec2<usize>) -> Option<vec2<isize>> {
+        DIRECTIONS
+            .into_iter()
+            .find(|&dir| self.add_dir(from, dir) == to)
     }
 
+    /// Cell reached by a step in `dir`, crossing the edges as the [Topology] says
+    /// and going through portals.
+    ///
+    /// Stepping off a non-wrapping edge stays at `pos`,
+    /// portals leading into each other in a loop are left as the portal cell.
     pub fn add_dir(&self, pos: vec2<usize>, dir: vec2<isize>) -> vec2<usize> {
-        pos.zip(dir)
-            .zip(self.size())
-            .map(|((pos, dir), size)| (pos as isize + size as isize + dir) as usize % size)
+        let mut next = self.wrap(pos, dir);
+        for _ in 0..self.portals.len() * 2 {
+            let Some(exit) = self.portal_exit(next) else {
+                break;
+            };
+            next = self.wrap(exit, dir);
+        }
+        next
+    }
+
+    fn wrap(&self, pos: vec2<usize>, dir: vec2<isize>) -> vec2<usize> {
+        let size = self.size().map(|x| x as isize);
+        let next = pos.map(|x| x as isize) + dir;
+        let off_edge = next.zip(size).map(|(x, size)| x < 0 || x >= size);
+        let wraps = self.header.topology.wraps();
+        if (off_edge.x && !wraps.x) || (off_edge.y && !wraps.y) {
+            return pos;
+        }
+        let mut next = next.zip(size).map(|(x, size)| x.rem_euclid(size));
+        if off_edge.x && self.header.topology == Topology::KleinBottle {
+            next.y = size.y - 1 - next.y;
+        }
+        next.map(|x| x as usize)
+    }
+
+    /// Pair of the portal at `pos`
+    fn portal_exit(&self, pos: vec2<usize>) -> Option<vec2<usize>> {
+        let MapCell::Portal(number) = self[pos] else {
+            return None;
+        };
+        match self.portals[&number][..] {
+            [a, b] => Some(if a == pos { b } else { a }),
+            _ => None,
+        }
+    }
+
+    /// Every portal with its pair, both ways around
+    fn portal_pairs(&self) -> impl Iterator<Item = (vec2<usize>, vec2<usize>)> + '_ {
+        self.portals.values().flat_map(|portals| match portals[..] {
+            [a, b] => vec![(a, b), (b, a)],
+            _ => vec![],
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (vec2<usize>, &MapCell)> + '_ {
@@ -65,58 +486,193 @@ impl Map {
         })
     }
 
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (vec2<usize>, &mut MapCell)> + '_ {
-        self.cells.iter_mut().enumerate().flat_map(|(x, row)| {
-            row.iter_mut()
-                .enumerate()
-                .map(move |(y, cell)| (vec2(x, y), cell))
-        })
+    /// Copy of every cell with its position, in [Self::iter] order
+    pub fn cells(&self) -> Vec<(vec2<usize>, MapCell)> {
+        self.iter().map(|(pos, cell)| (pos, cell.clone())).collect()
     }
+
     pub fn size(&self) -> vec2<usize> {
         vec2(self.cells.len(), self.cells[0].len())
     }
-    pub fn parse(s: &str) -> Self {
-        Self {
-            cells: {
-                let mut cells: Vec<Vec<MapCell>> = vec![];
-                for (y, line) in s.lines().enumerate() {
-                    for (x, c) in line.chars().enumerate() {
-                        let cell = match c {
-                            ' ' => MapCell::Empty,
-                            '#' => MapCell::Wall,
-                            _ => {
-                                panic!("Unexpected character {c:?}");
-                            }
-                        };
-                        cells.resize_with(cells.len().max(x + 1), default);
-                        let row = &mut cells[x];
-                        row.resize_with(row.len().max(y + 1), default);
-                        row[y] = cell;
+    /// Parse a map file.
+    ///
+    /// An optional TOML header (see [MapHeader]) can precede the grid,
+    /// separated from it by a `---` line. Grid legend:
+    /// - `#` - wall
+    /// - `@` - player spawn point
+    /// - `<`, `>`, `^`, `v` - snake head facing that direction,
+    ///   its body is traced through adjacent `o` cells
+    /// - `f`, `r`, `u`, `d`, `s`, `z`, `w`, `y`, `a`, `t` - pre-placed item, see [Item::map_char]
+    /// - `0` to `9` - portal, every number has to appear exactly twice
+    pub fn parse(s: &str) -> Result<Self, MapError> {
+        let lines: Vec<&str> = s.lines().collect();
+        let (header_source, grid_start) = match lines
+            .iter()
+            .position(|line| line.trim_end() == HEADER_SEPARATOR)
+        {
+            Some(i) => (lines[..i].join("\n"), i + 1),
+            None => (String::new(), 0),
+        };
+        let grid = &lines[grid_start..];
+        let header: MapHeader = toml::from_str(&header_source).map_err(MapError::Header)?;
+
+        let mut player_spawns = Vec::new();
+        let mut snake_heads = Vec::new();
+        let mut snake_bodies = HashSet::new();
+        let mut portals: HashMap<u8, Vec<vec2<usize>>> = HashMap::new();
+        let mut cells: Vec<Vec<MapCell>> = vec![];
+        for (y, line) in grid.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let pos = vec2(x, y);
+                let cell = match c {
+                    ' ' => MapCell::Empty,
+                    '#' => MapCell::Wall,
+                    PLAYER_SPAWN_CHAR => {
+                        player_spawns.push(pos);
+                        MapCell::Empty
                     }
-                }
-                let height = cells.iter().map(|row| row.len()).max().unwrap();
-                for row in &mut cells {
-                    row.resize_with(height, default);
-                    row.reverse();
-                }
-                cells
-            },
+                    BODY_CHAR => {
+                        snake_bodies.insert(pos);
+                        MapCell::Empty
+                    }
+                    _ => {
+                        if let Some(item) = Item::from_map_char(c) {
+                            MapCell::Item(item)
+                        } else if let Some(number) = c.to_digit(10) {
+                            let number = number as u8;
+                            portals.entry(number).or_default().push(pos);
+                            MapCell::Portal(number)
+                        } else if let Some(&(_, dir)) =
+                            SNAKE_HEAD_CHARS.iter().find(|&&(head, _)| head == c)
+                        {
+                            snake_heads.push((pos, dir));
+                            MapCell::Empty
+                        } else {
+                            return Err(MapError::UnexpectedCharacter {
+                                line: grid_start + y + 1,
+                                column: x + 1,
+                                character: c,
+                            });
+                        }
+                    }
+                };
+                cells.resize_with(cells.len().max(x + 1), default);
+                let row = &mut cells[x];
+                row.resize_with(row.len().max(y + 1), default);
+                row[y] = cell;
+            }
+        }
+        let Some(height) = cells.iter().map(|row| row.len()).max() else {
+            return Err(MapError::Empty);
+        };
+        if let Some((&number, positions)) = portals
+            .iter()
+            .filter(|(_, positions)| positions.len() != 2)
+            .min_by_key(|(_, positions)| (positions[0].y, positions[0].x))
+        {
+            return Err(MapError::UnpairedPortal {
+                line: grid_start + positions[0].y + 1,
+                column: positions[0].x + 1,
+                number,
+            });
         }
+        for row in &mut cells {
+            row.resize_with(height, default);
+            row.reverse();
+        }
+        let flip = |pos: vec2<usize>| vec2(pos.x, height - 1 - pos.y);
+
+        let mut map = Self::from_cells(cells);
+        map.header = header;
+        map.header_source = header_source;
+        map.player_spawns = player_spawns.into_iter().map(flip).collect();
+        let mut snake_bodies: HashSet<vec2<usize>> = snake_bodies.into_iter().map(flip).collect();
+        for (head, dir) in snake_heads {
+            let head = flip(head);
+            let mut body = vec![head];
+            let mut behind = -dir;
+            loop {
+                let current = *body.last().unwrap();
+                // Prefer going straight so that parallel bodies are traced correctly
+                let next = std::iter::once(behind)
+                    .chain(DIRECTIONS)
+                    .map(|dir| (dir, map.add_dir(current, dir)))
+                    .find(|(_, pos)| snake_bodies.contains(pos));
+                let Some((dir, next)) = next else { break };
+                snake_bodies.remove(&next);
+                body.push(next);
+                behind = dir;
+            }
+            body.reverse();
+            map.snake_spawns.push(SnakeSpawn { body, dir });
+        }
+        if let Some(pos) = snake_bodies
+            .into_iter()
+            .map(flip)
+            .min_by_key(|pos| (pos.y, pos.x))
+        {
+            return Err(MapError::DetachedSnakeBody {
+                line: grid_start + pos.y + 1,
+                column: pos.x + 1,
+            });
+        }
+        let snake_cells: HashSet<vec2<usize>> = map
+            .snake_spawns
+            .iter()
+            .flat_map(|spawn| spawn.body.iter().copied())
+            .collect();
+        if !map
+            .iter()
+            .any(|(pos, cell)| matches!(cell, MapCell::Empty) && !snake_cells.contains(&pos))
+        {
+            return Err(MapError::NoRoom);
+        }
+        Ok(map)
     }
 
-    pub fn save(&self, path: impl AsRef<std::path::Path>) {
-        let f = std::fs::File::create(path).unwrap();
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let mut chars: Vec<Vec<char>> = self
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        MapCell::Wall => '#',
+                        MapCell::Item(item) => item.map_char(),
+                        &MapCell::Portal(number) => char::from_digit(number.into(), 10).unwrap(),
+                        _ => ' ',
+                    })
+                    .collect()
+            })
+            .collect();
+        for pos in &self.player_spawns {
+            chars[pos.x][pos.y] = PLAYER_SPAWN_CHAR;
+        }
+        for spawn in &self.snake_spawns {
+            for pos in &spawn.body {
+                chars[pos.x][pos.y] = BODY_CHAR;
+            }
+            let head = spawn.body.last().unwrap();
+            chars[head.x][head.y] = SNAKE_HEAD_CHARS
+                .iter()
+                .find(|&&(_, dir)| dir == spawn.dir)
+                .unwrap()
+                .0;
+        }
+
+        let f = std::fs::File::create(path)?;
         let mut writer = std::io::BufWriter::new(f);
+        if !self.header_source.is_empty() {
+            writeln!(writer, "{}", self.header_source)?;
+            writeln!(writer, "{HEADER_SEPARATOR}")?;
+        }
         for y in (0..self.size().y).rev() {
             for x in 0..self.size().x {
-                let c = match self.cells[x][y] {
-                    MapCell::Wall => '#',
-                    _ => ' ',
-                };
-                write!(writer, "{c}").unwrap();
+                write!(writer, "{}", chars[x][y])?;
             }
-            writeln!(writer).unwrap();
+            writeln!(writer)?;
         }
+        writer.flush()
     }
 }
 
@@ -127,8 +683,84 @@ impl Index<vec2<usize>> for Map {
     }
 }
 
-impl IndexMut<vec2<usize>> for Map {
-    fn index_mut(&mut self, pos: vec2<usize>) -> &mut MapCell {
-        &mut self.cells[pos.x][pos.y]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(s: &str) -> MapError {
+        match Map::parse(s) {
+            Ok(_) => panic!("{s:?} parsed without errors"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let e = parse_error("name = \"test\"\n---\n###\n# ?\n###");
+        assert!(
+            matches!(
+                e,
+                MapError::UnexpectedCharacter {
+                    line: 4,
+                    column: 3,
+                    character: '?',
+                }
+            ),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn unpaired_portal() {
+        let e = parse_error("#####\n#1 2#\n# 1 #\n#1  #");
+        assert!(
+            matches!(
+                e,
+                MapError::UnpairedPortal {
+                    line: 2,
+                    column: 2,
+                    number: 1,
+                }
+            ),
+            "{e:?}"
+        );
+        let e = parse_error("#1 2#\n#1  #");
+        assert!(
+            matches!(
+                e,
+                MapError::UnpairedPortal {
+                    line: 1,
+                    column: 4,
+                    number: 2,
+                }
+            ),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn detached_snake_body() {
+        let e = parse_error("#####\n# o>#\n#   #\n#o  #\n#####");
+        assert!(
+            matches!(e, MapError::DetachedSnakeBody { line: 4, column: 2 }),
+            "{e:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_header() {
+        let e = match parse_error("name = \"test\"\nauthor =\n---\n#") {
+            MapError::Header(e) => e,
+            e => panic!("{e:?}"),
+        };
+        // Header lines are counted from zero, the same as in the file
+        assert_eq!(e.line_col().map(|(line, _)| line), Some(1));
+    }
+
+    #[test]
+    fn no_room() {
+        assert!(matches!(parse_error("#"), MapError::NoRoom));
+        // Cells taken by snakes do not count
+        assert!(matches!(parse_error("#o>#"), MapError::NoRoom));
     }
 }
@@ -0,0 +1,113 @@
+use super::*;
+
+/// Menu navigation event, shared by all menu states
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+impl MenuInput {
+    pub fn from_event(controls: &Controls, event: &geng::Event) -> Option<Self> {
+        let geng::Event::KeyPress { key } = event else {
+            return None;
+        };
+        Some(if controls.up.contains(key) {
+            Self::Up
+        } else if controls.down.contains(key) {
+            Self::Down
+        } else if controls.left.contains(key) {
+            Self::Left
+        } else if controls.right.contains(key) {
+            Self::Right
+        } else if controls.use_item.contains(key) {
+            Self::Select
+        } else if *key == geng::Key::Escape {
+            Self::Back
+        } else {
+            return None;
+        })
+    }
+}
+
+/// Vertical list of entries with one of them selected
+pub struct Menu {
+    pub title: String,
+    pub entries: Vec<String>,
+    pub selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: &str, entries: Vec<String>) -> Self {
+        Self {
+            title: title.to_owned(),
+            entries,
+            selected: 0,
+        }
+    }
+
+    /// Move the selection, returns the remaining input if it was not about that
+    pub fn navigate(&mut self, input: MenuInput) -> Option<MenuInput> {
+        let len = self.entries.len();
+        match input {
+            MenuInput::Up => self.selected = (self.selected + len - 1) % len,
+            MenuInput::Down => self.selected = (self.selected + 1) % len,
+            _ => return Some(input),
+        }
+        None
+    }
+
+    pub fn draw(&self, ctx: &Context, framebuffer: &mut ugli::Framebuffer, hint: &str) {
+        let config = &ctx.assets.config;
+        ugli::clear(framebuffer, Some(config.colors.background), None, None);
+        let camera = geng::Camera2d {
+            center: vec2::ZERO,
+            rotation: Angle::ZERO,
+            fov: config.ui_fov,
+        };
+        ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            &self.title,
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, 3.5)),
+            Rgba::WHITE,
+            config.outline_size,
+            config.outline_color,
+        );
+        // Long menus get squeezed to fit between the title and the hint
+        let spacing = (4.8 / self.entries.len() as f32).min(0.9);
+        for (i, entry) in self.entries.iter().enumerate() {
+            let color = if i == self.selected {
+                config.colors.player
+            } else {
+                Rgba::WHITE
+            };
+            ctx.assets.font.draw_with_outline(
+                framebuffer,
+                &camera,
+                entry,
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(vec2(0.0, 1.5 - i as f32 * spacing))
+                    * mat3::scale_uniform(spacing * 2.0 / 3.0),
+                color,
+                config.outline_size,
+                config.outline_color,
+            );
+        }
+        ctx.assets.font.draw_with_outline(
+            framebuffer,
+            &camera,
+            hint,
+            vec2::splat(geng::TextAlign::CENTER),
+            mat3::translate(vec2(0.0, -4.0)) * mat3::scale_uniform(0.3),
+            Rgba::WHITE,
+            config.outline_size,
+            config.outline_color,
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use super::*;
+
+use simulation::{Event, Input, Results, Role};
+use std::io::{BufRead, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+
+/// Port [server] listens on unless told otherwise
+pub const DEFAULT_PORT: u16 = 1155;
+
+/// How long to wait for the server to accept a client
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Input of the client's player, sent whenever it changes
+    Input(Input),
+    /// Start a new round once the current one is over
+    Restart,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome(Welcome),
+    /// Number of players the server waits for before the game goes on
+    Waiting {
+        missing: usize,
+    },
+    Update(Update),
+}
+
+/// Full game state, sent on connecting and after every restart
+#[derive(Serialize, Deserialize)]
+pub struct Welcome {
+    /// Index of the player controlled by the receiving client
+    pub player: usize,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub roles: Vec<Role>,
+    /// Ids of the players' runners or steered snakes
+    pub player_ids: Vec<Option<Id>>,
+    /// Map file the game was started from
+    pub map: String,
+    pub cells: Vec<(vec2<usize>, MapCell)>,
+}
+
+impl Welcome {
+    pub fn new(sim: &Simulation, player: usize, difficulty: Difficulty, map: &str) -> Self {
+        Self {
+            player,
+            seed: sim.seed,
+            difficulty,
+            roles: sim.players.iter().map(|player| player.role).collect(),
+            player_ids: sim.players.iter().map(|player| player.id).collect(),
+            map: map.to_owned(),
+            cells: sim.map.cells(),
+        }
+    }
+
+    /// Mirror of the server's simulation, never ticked itself
+    pub fn simulation(self, base_config: &Config) -> anyhow::Result<Simulation> {
+        let map = Map::parse(&self.map)?;
+        let config = base_config.for_map(&map.header, self.difficulty);
+        let mut sim = Simulation::new(Rc::new(config), map, self.seed, &self.roles);
+        for (pos, cell) in self.cells {
+            sim.map.set(pos, cell);
+        }
+        for (player, id) in sim.players.iter_mut().zip(self.player_ids) {
+            player.id = id;
+        }
+        Ok(sim)
+    }
+}
+
+/// Player state shown by clients
+#[derive(Serialize, Deserialize)]
+pub struct PlayerState {
+    pub id: Option<Id>,
+    pub held_item: Option<Item>,
+    pub score: usize,
+    pub caught: bool,
+}
+
+/// Changes made by a single server tick
+#[derive(Serialize, Deserialize)]
+pub struct Update {
+    /// Cells that changed since the previous update
+    pub cells: Vec<(vec2<usize>, MapCell)>,
+    pub timers: Vec<(vec2<usize>, f64)>,
+    pub players: Vec<PlayerState>,
+    pub time: f64,
+    pub score: usize,
+    pub player_moved: bool,
+    pub snake_frozen: Vec<(Id, f64)>,
+    pub results: Option<Results>,
+    pub events: Vec<Event>,
+}
+
+impl Update {
+    /// Changes since `sent_cells`, which are updated to the current cells
+    pub fn new(
+        sim: &Simulation,
+        sent_cells: &mut Vec<(vec2<usize>, MapCell)>,
+        events: Vec<Event>,
+    ) -> Self {
+        let current = sim.map.cells();
+        let changed = current
+            .iter()
+            .zip(sent_cells.iter())
+            .filter(|(cell, sent)| cell != sent)
+            .map(|(cell, _)| cell.clone())
+            .collect();
+        *sent_cells = current;
+        Self {
+            cells: changed,
+            timers: sim.map.timers(),
+            players: sim
+                .players
+                .iter()
+                .map(|player| PlayerState {
+                    id: player.id,
+                    held_item: player.held_item.clone(),
+                    score: player.score,
+                    caught: player.caught,
+                })
+                .collect(),
+            time: sim.time,
+            score: sim.score,
+            player_moved: sim.player_moved,
+            snake_frozen: sim
+                .snake_frozen
+                .iter()
+                .map(|(&id, &time_left)| (id, time_left))
+                .collect(),
+            results: sim.results.clone(),
+            events,
+        }
+    }
+
+    /// Bring a mirrored simulation up to date, returns the events to present
+    pub fn apply(self, sim: &mut Simulation) -> Vec<Event> {
+        for (pos, cell) in self.cells {
+            sim.map.set(pos, cell);
+        }
+        for (pos, time_left) in self.timers {
+            let cell = sim.map[pos].clone();
+            sim.map.set_timed(pos, cell, time_left);
+        }
+        for (player, state) in sim.players.iter_mut().zip(self.players) {
+            player.id = state.id;
+            player.held_item = state.held_item;
+            player.score = state.score;
+            player.caught = state.caught;
+        }
+        sim.time = self.time;
+        sim.score = self.score;
+        sim.player_moved = self.player_moved;
+        sim.snake_frozen = self.snake_frozen.into_iter().collect();
+        sim.results = self.results;
+        self.events
+    }
+}
+
+/// Messages are sent as JSON, one per line
+pub fn encode(message: &impl Serialize) -> serde_json::Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+pub fn send(stream: &mut TcpStream, message: &impl Serialize) -> std::io::Result<()> {
+    stream.write_all(&encode(message)?)
+}
+
+/// Messages read from `stream` until it is closed, blocks while waiting for them
+pub fn messages<T: serde::de::DeserializeOwned>(stream: TcpStream) -> impl Iterator<Item = T> {
+    std::io::BufReader::new(stream)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| match serde_json::from_str(&line) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                log::warn!("Ignoring invalid message: {e}");
+                None
+            }
+        })
+}
+
+/// Connection of a game client to [server]
+pub struct Client {
+    /// Index of the player controlled from this client
+    pub player: usize,
+    /// Players the server still waits for
+    pub missing_players: usize,
+    stream: TcpStream,
+    messages: mpsc::Receiver<ServerMessage>,
+    /// Held directions last sent, presses are sent as they happen
+    sent_held_dirs: Vec<vec2<isize>>,
+}
+
+impl Client {
+    /// Connect to `addr` and wait for the game state
+    pub fn connect(addr: &str) -> anyhow::Result<(Self, Welcome)> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let (sender, receiver) = mpsc::channel();
+        let reader = stream.try_clone()?;
+        std::thread::spawn(move || {
+            for message in messages(reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let welcome = loop {
+            match receiver.recv_timeout(CONNECT_TIMEOUT)? {
+                ServerMessage::Welcome(welcome) => break welcome,
+                _ => continue,
+            }
+        };
+        log::info!("Connected to {addr} as player {}", welcome.player + 1);
+        Ok((
+            Self {
+                player: welcome.player,
+                missing_players: 0,
+                stream,
+                messages: receiver,
+                sent_held_dirs: Vec::new(),
+            },
+            welcome,
+        ))
+    }
+
+    pub fn send_input(&mut self, input: Input) -> anyhow::Result<()> {
+        if input.moves.is_empty() && !input.use_item && input.held_dirs == self.sent_held_dirs {
+            return Ok(());
+        }
+        self.sent_held_dirs = input.held_dirs.clone();
+        send(&mut self.stream, &ClientMessage::Input(input))?;
+        Ok(())
+    }
+
+    pub fn request_restart(&mut self) -> anyhow::Result<()> {
+        send(&mut self.stream, &ClientMessage::Restart)?;
+        Ok(())
+    }
+
+    /// Messages received since the last call, an error once the server is gone
+    pub fn receive(&mut self) -> anyhow::Result<Vec<ServerMessage>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.messages.try_recv() {
+                Ok(ServerMessage::Waiting { missing }) => self.missing_players = missing,
+                Ok(message) => messages.push(message),
+                Err(mpsc::TryRecvError::Empty) => return Ok(messages),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    anyhow::bail!("Disconnected from the server")
+                }
+            }
+        }
+    }
+}
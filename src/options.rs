@@ -0,0 +1,130 @@
+use super::*;
+
+use menu::{Menu, MenuInput};
+
+/// Player settings, persisted between runs
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Options {
+    pub volume: f64,
+    pub music_volume: f64,
+    /// Rebound controls, replacing the ones from [Config]
+    #[serde(default)]
+    pub controls: Option<Controls>,
+}
+
+impl Options {
+    const KEY: &'static str = "options";
+
+    /// Saved options, or the defaults from [Config]
+    pub fn load(config: &Config) -> Self {
+        preferences::load(Self::KEY).unwrap_or_else(|| Self {
+            volume: config.volume,
+            music_volume: config.music_volume,
+            controls: None,
+        })
+    }
+
+    pub fn save(&self) {
+        preferences::save(Self::KEY, self);
+    }
+}
+
+const VOLUME: usize = 0;
+const MUSIC_VOLUME: usize = 1;
+const CONTROLS: usize = 2;
+const FULLSCREEN: usize = 3;
+const BACK: usize = 4;
+
+/// Volume step when changing it with left/right
+const VOLUME_STEP: f64 = 0.1;
+
+/// Pushed on top of the title screen or the pause menu
+pub struct OptionsMenu {
+    ctx: Context,
+    options: Options,
+    menu: Menu,
+    transition: Option<geng::state::Transition>,
+}
+
+impl OptionsMenu {
+    pub fn new(ctx: &Context) -> Self {
+        let mut result = Self {
+            ctx: ctx.clone(),
+            options: Options::load(&ctx.assets.config),
+            menu: Menu::new("Options", Vec::new()),
+            transition: None,
+        };
+        result.update_entries();
+        result
+    }
+
+    fn update_entries(&mut self) {
+        let percent = |volume: f64| (volume * 100.0).round() as i32;
+        self.menu.entries = vec![
+            format!("< volume: {}% >", percent(self.options.volume)),
+            format!("< music volume: {}% >", percent(self.options.music_volume)),
+            "Controls".to_owned(),
+            "Toggle fullscreen".to_owned(),
+            "Back".to_owned(),
+        ];
+    }
+
+    fn change_volume(&mut self, delta: f64) {
+        // Other menus save options too, so keep what they changed meanwhile
+        self.options = Options::load(&self.ctx.assets.config);
+        let volume = match self.menu.selected {
+            VOLUME => &mut self.options.volume,
+            MUSIC_VOLUME => &mut self.options.music_volume,
+            _ => return,
+        };
+        *volume = (*volume + delta).clamp(0.0, 1.0);
+        self.ctx.geng.audio().set_volume(self.options.volume);
+        self.options.save();
+        self.update_entries();
+    }
+}
+
+impl geng::State for OptionsMenu {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        let controls = self.ctx.controls.borrow().clone();
+        if let geng::Event::KeyPress { key } = event {
+            if controls.fullscreen.contains(&key) {
+                self.ctx.geng.window().toggle_fullscreen();
+            }
+        }
+        let Some(input) = MenuInput::from_event(&controls, &event) else {
+            return;
+        };
+        match self.menu.navigate(input) {
+            Some(MenuInput::Left) => self.change_volume(-VOLUME_STEP),
+            Some(MenuInput::Right) => self.change_volume(VOLUME_STEP),
+            Some(MenuInput::Back) => self.transition = Some(geng::state::Transition::Pop),
+            Some(MenuInput::Select) => match self.menu.selected {
+                CONTROLS => {
+                    self.transition = Some(geng::state::Transition::Push(Box::new(
+                        controls_menu::ControlsMenu::new(&self.ctx),
+                    )));
+                }
+                FULLSCREEN => self.ctx.geng.window().toggle_fullscreen(),
+                BACK => self.transition = Some(geng::state::Transition::Pop),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        self.menu.draw(
+            &self.ctx,
+            framebuffer,
+            "Up/Down to select, Left/Right to change, Escape to go back",
+        );
+    }
+}
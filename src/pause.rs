@@ -0,0 +1,96 @@
+use super::*;
+
+use menu::{Menu, MenuInput};
+
+/// What was picked in the pause menu, carried out by [Game] once it is back on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseAction {
+    Resume,
+    Save,
+    Restart,
+    Quit,
+}
+
+const RESUME: usize = 0;
+const SAVE: usize = 1;
+const RESTART: usize = 2;
+const OPTIONS: usize = 3;
+const QUIT: usize = 4;
+
+/// Pushed on top of [Game], which is not updated until this is popped
+pub struct Pause {
+    ctx: Context,
+    menu: Menu,
+    action: Rc<std::cell::Cell<Option<PauseAction>>>,
+    transition: Option<geng::state::Transition>,
+}
+
+impl Pause {
+    pub fn new(ctx: &Context, action: Rc<std::cell::Cell<Option<PauseAction>>>) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            menu: Menu::new(
+                "Paused",
+                vec![
+                    "Resume".to_owned(),
+                    "Save".to_owned(),
+                    "Restart".to_owned(),
+                    "Options".to_owned(),
+                    "Quit to level select".to_owned(),
+                ],
+            ),
+            action,
+            transition: None,
+        }
+    }
+
+    fn close(&mut self, action: PauseAction) {
+        self.action.set(Some(action));
+        self.transition = Some(geng::state::Transition::Pop);
+    }
+}
+
+impl geng::State for Pause {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        let controls = self.ctx.controls.borrow().clone();
+        if let geng::Event::KeyPress { key } = event {
+            if controls.fullscreen.contains(&key) {
+                self.ctx.geng.window().toggle_fullscreen();
+            }
+        }
+        let Some(input) = MenuInput::from_event(&controls, &event) else {
+            return;
+        };
+        match self.menu.navigate(input) {
+            Some(MenuInput::Back) => self.close(PauseAction::Resume),
+            Some(MenuInput::Select) => match self.menu.selected {
+                RESUME => self.close(PauseAction::Resume),
+                SAVE => self.close(PauseAction::Save),
+                RESTART => self.close(PauseAction::Restart),
+                OPTIONS => {
+                    self.transition = Some(geng::state::Transition::Push(Box::new(
+                        options::OptionsMenu::new(&self.ctx),
+                    )));
+                }
+                QUIT => self.close(PauseAction::Quit),
+                _ => unreachable!(),
+            },
+            _ => {}
+        }
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let hint = format!(
+            "Up/Down to select, {} to confirm, Escape to resume",
+            Controls::key_name(&self.ctx.controls.borrow().use_item)
+        );
+        self.menu.draw(&self.ctx, framebuffer, &hint);
+    }
+}
@@ -0,0 +1,86 @@
+use super::*;
+
+use simulation::{Input, Role};
+
+/// Everything needed to reproduce a run exactly
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    pub roles: Vec<Role>,
+    pub map: String,
+    /// Inputs of all players with the tick they were applied at, ticks without any are skipped
+    pub inputs: Vec<(u64, Vec<Input>)>,
+    /// Ticks at which saving the game reset the random generator, with the seed used
+    #[serde(default)]
+    pub reseeds: Vec<(u64, u64)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, difficulty: Difficulty, roles: &[Role], map: &str) -> Self {
+        Self {
+            seed,
+            difficulty,
+            roles: roles.to_vec(),
+            map: map.to_owned(),
+            inputs: Vec::new(),
+            reseeds: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, inputs: &[Input]) {
+        if inputs.iter().any(|input| *input != Input::default()) {
+            self.inputs.push((tick, inputs.to_vec()));
+        }
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
+
+/// Feeds recorded inputs back tick by tick
+pub struct Playback {
+    inputs: std::collections::VecDeque<(u64, Vec<Input>)>,
+    reseeds: std::collections::VecDeque<(u64, u64)>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            inputs: replay.inputs.into(),
+            reseeds: replay.reseeds.into(),
+        }
+    }
+
+    /// Seed to reset the random generator to before given tick, if the game was saved there
+    pub fn reseed(&mut self, tick: u64) -> Option<u64> {
+        match self.reseeds.front() {
+            Some(&(reseed_tick, seed)) if reseed_tick == tick => {
+                self.reseeds.pop_front();
+                Some(seed)
+            }
+            _ => None,
+        }
+    }
+
+    /// Inputs of all players for given tick, empty if nobody pressed anything
+    pub fn inputs(&mut self, tick: u64) -> Vec<Input> {
+        match self.inputs.front() {
+            Some((input_tick, _)) if *input_tick == tick => self.inputs.pop_front().unwrap().1,
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::*;
+
+/// Run in progress, resumed with `--load` or the quickload key
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    /// Campaign level being played, `None` for the endless mode
+    pub level: Option<usize>,
+    /// Inputs up to the save, so that a resumed run still gets a complete replay
+    pub replay: Replay,
+    pub versus: Option<Versus>,
+    pub snapshot: simulation::Snapshot,
+}
+
+impl SaveGame {
+    /// File written by quicksave and read by quickload
+    pub fn quicksave_path() -> std::path::PathBuf {
+        run_dir().join("saves").join("quicksave.json")
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
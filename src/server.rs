@@ -0,0 +1,368 @@
+//! Headless host of networked games, clients join with `--connect`
+
+use super::*;
+
+use net::{ClientMessage, ServerMessage};
+use simulation::{Input, Role};
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+
+/// Messages a client can fall behind on before it is dropped
+const OUTGOING_LIMIT: usize = 256;
+
+#[derive(clap::Parser)]
+struct ServerArgs {
+    /// Address to listen on
+    #[clap(long, default_value_t = format!("0.0.0.0:{}", net::DEFAULT_PORT))]
+    addr: String,
+    /// Number of players to wait for before the game starts
+    #[clap(long, default_value_t = 2)]
+    players: usize,
+    /// The last player to join steers a snake instead of running
+    #[clap(long)]
+    versus: bool,
+    /// Map file to play, `assets/map.txt` if not specified
+    #[clap(long)]
+    map: Option<std::path::PathBuf>,
+    /// Seed of the first round, later rounds get random ones
+    #[clap(long)]
+    seed: Option<u64>,
+    #[clap(long, value_enum, default_value_t)]
+    difficulty: Difficulty,
+}
+
+/// Things happening on the connection threads, tagged with the player and connection id
+enum Incoming {
+    Connected(TcpStream),
+    Message(usize, u64, ClientMessage),
+    Disconnected(usize, u64),
+}
+
+/// Connected player, written to from a thread of its own
+/// so that a slow client can not hold up the game
+struct Connection {
+    /// Tells this connection apart from earlier ones of the same player
+    id: u64,
+    stream: TcpStream,
+    outgoing: mpsc::SyncSender<Vec<u8>>,
+}
+
+struct Server {
+    config: Config,
+    difficulty: Difficulty,
+    map_source: String,
+    roles: Vec<Role>,
+    sim: Simulation,
+    /// Connection of each player, `None` until someone joins as them
+    clients: Vec<Option<Connection>>,
+    next_connection_id: u64,
+    /// Input received since the last tick, held directions stay until changed
+    inputs: Vec<Input>,
+    /// Cells as clients last saw them
+    sent_cells: Vec<(vec2<usize>, MapCell)>,
+    incoming: mpsc::Sender<Incoming>,
+}
+
+impl Server {
+    fn new(args: &ServerArgs, incoming: mpsc::Sender<Incoming>) -> anyhow::Result<Self> {
+        let assets = run_dir().join("assets");
+        let config: Config = toml::from_str(&std::fs::read_to_string(assets.join("config.toml"))?)?;
+        let map_path = args.map.clone().unwrap_or_else(|| assets.join("map.txt"));
+        let map_source = std::fs::read_to_string(map_path)?;
+        let players = args.players.max(1);
+        let mut roles = vec![Role::Runner; players];
+        if args.versus && players > 1 {
+            roles[players - 1] = Role::Snake;
+        }
+        let seed = args.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut server = Self {
+            sim: Self::simulation(&config, &map_source, seed, args.difficulty, &roles)?,
+            config,
+            difficulty: args.difficulty,
+            map_source,
+            clients: std::iter::repeat_with(|| None).take(players).collect(),
+            next_connection_id: 0,
+            inputs: vec![default(); players],
+            sent_cells: Vec::new(),
+            roles,
+            incoming,
+        };
+        server.sent_cells = server.sim.map.cells();
+        Ok(server)
+    }
+
+    fn simulation(
+        config: &Config,
+        map_source: &str,
+        seed: u64,
+        difficulty: Difficulty,
+        roles: &[Role],
+    ) -> anyhow::Result<Simulation> {
+        let map = Map::parse(map_source)?;
+        let config = config.for_map(&map.header, difficulty);
+        Ok(Simulation::new(Rc::new(config), map, seed, roles))
+    }
+
+    fn welcome(&self, player: usize) -> ServerMessage {
+        ServerMessage::Welcome(net::Welcome::new(
+            &self.sim,
+            player,
+            self.difficulty,
+            &self.map_source,
+        ))
+    }
+
+    /// Whether the event came from whoever is playing as `player` right now,
+    /// a dropped client can still have some left over after its slot was taken
+    fn is_connected(&self, player: usize, id: u64) -> bool {
+        self.clients[player]
+            .as_ref()
+            .is_some_and(|connection| connection.id == id)
+    }
+
+    fn missing_players(&self) -> usize {
+        self.clients
+            .iter()
+            .filter(|client| client.is_none())
+            .count()
+    }
+
+    fn send(&mut self, player: usize, message: &ServerMessage) {
+        if let Some(line) = Self::encode(message) {
+            self.send_line(player, line);
+        }
+    }
+
+    fn broadcast(&mut self, message: &ServerMessage) {
+        let Some(line) = Self::encode(message) else {
+            return;
+        };
+        for player in 0..self.clients.len() {
+            self.send_line(player, line.clone());
+        }
+    }
+
+    fn encode(message: &ServerMessage) -> Option<Vec<u8>> {
+        match net::encode(message) {
+            Ok(line) => Some(line),
+            Err(e) => {
+                log::error!("Failed to encode a message: {e}");
+                None
+            }
+        }
+    }
+
+    fn send_line(&mut self, player: usize, line: Vec<u8>) {
+        let Some(connection) = &self.clients[player] else {
+            return;
+        };
+        // A full queue means the client stopped reading, waiting for it would stall everyone
+        if let Err(e) = connection.outgoing.try_send(line) {
+            log::warn!("Failed to send to player {}: {e}", player + 1);
+            self.disconnect(player);
+        }
+    }
+
+    fn connect(&mut self, stream: TcpStream) {
+        let addr = stream.peer_addr().ok();
+        let Some(player) = self.clients.iter().position(|client| client.is_none()) else {
+            log::info!("Rejected {addr:?}, the game is full");
+            return;
+        };
+        let streams = stream
+            .try_clone()
+            .and_then(|reader| Ok((reader, stream.try_clone()?)));
+        let (reader, mut writer) = match streams {
+            Ok(streams) => streams,
+            Err(e) => {
+                log::warn!("Failed to accept {addr:?}: {e}");
+                return;
+            }
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            log::warn!("Failed to set up {addr:?}: {e}");
+        }
+        log::info!("{addr:?} joined as player {}", player + 1);
+        let (outgoing, lines) = mpsc::sync_channel::<Vec<u8>>(OUTGOING_LIMIT);
+        std::thread::spawn(move || {
+            for line in lines {
+                if writer.write_all(&line).is_err() {
+                    return;
+                }
+            }
+        });
+        let id = self.next_connection_id;
+        self.next_connection_id += 1;
+        self.clients[player] = Some(Connection {
+            id,
+            stream,
+            outgoing,
+        });
+        self.inputs[player] = default();
+        let incoming = self.incoming.clone();
+        std::thread::spawn(move || {
+            for message in net::messages(reader) {
+                if incoming
+                    .send(Incoming::Message(player, id, message))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            incoming.send(Incoming::Disconnected(player, id)).ok();
+        });
+        let welcome = self.welcome(player);
+        self.send(player, &welcome);
+        self.broadcast(&ServerMessage::Waiting {
+            missing: self.missing_players(),
+        });
+    }
+
+    fn disconnect(&mut self, player: usize) {
+        if let Some(connection) = self.clients[player].take() {
+            log::info!("Player {} left", player + 1);
+            connection.stream.shutdown(std::net::Shutdown::Both).ok();
+            self.broadcast(&ServerMessage::Waiting {
+                missing: self.missing_players(),
+            });
+        }
+    }
+
+    fn handle_message(&mut self, player: usize, message: ClientMessage) {
+        match message {
+            ClientMessage::Input(input) => {
+                // Anything but a unit step would let clients jump through walls
+                let valid = |dir: &vec2<isize>| DIRECTIONS.contains(dir);
+                let pending = &mut self.inputs[player];
+                let room = self
+                    .config
+                    .move_queue_size
+                    .saturating_sub(pending.moves.len());
+                pending
+                    .moves
+                    .extend(input.moves.into_iter().filter(valid).take(room));
+                pending.use_item |= input.use_item;
+                pending.held_dirs = input
+                    .held_dirs
+                    .into_iter()
+                    .filter(valid)
+                    .take(DIRECTIONS.len())
+                    .collect();
+            }
+            ClientMessage::Restart => {
+                if self.sim.results.is_some() {
+                    self.restart();
+                }
+            }
+        }
+    }
+
+    fn restart(&mut self) {
+        let seed = thread_rng().gen();
+        match Self::simulation(
+            &self.config,
+            &self.map_source,
+            seed,
+            self.difficulty,
+            &self.roles,
+        ) {
+            Ok(sim) => self.sim = sim,
+            Err(e) => {
+                log::error!("Failed to restart: {e}");
+                return;
+            }
+        }
+        log::info!("Restarted with seed {seed}");
+        for input in &mut self.inputs {
+            *input = default();
+        }
+        for player in 0..self.clients.len() {
+            let welcome = self.welcome(player);
+            self.send(player, &welcome);
+        }
+        self.sent_cells = self.sim.map.cells();
+    }
+
+    fn tick(&mut self, delta_time: f64) {
+        // Presses are used up by the tick, held directions stay
+        let inputs: Vec<Input> = self
+            .inputs
+            .iter_mut()
+            .map(|input| Input {
+                moves: mem::take(&mut input.moves),
+                held_dirs: input.held_dirs.clone(),
+                use_item: mem::take(&mut input.use_item),
+            })
+            .collect();
+        let events = self.sim.tick(delta_time, &inputs);
+        let update = net::Update::new(&self.sim, &mut self.sent_cells, events);
+        self.broadcast(&ServerMessage::Update(update));
+    }
+}
+
+/// Run the server until it is killed
+pub fn run() {
+    logger::init();
+    let args: ServerArgs = cli::parse();
+    if let Err(e) = serve(&args) {
+        log::error!("{e:#}");
+        std::process::exit(1);
+    }
+}
+
+fn serve(args: &ServerArgs) -> anyhow::Result<()> {
+    let (sender, incoming) = mpsc::channel();
+    let mut server = Server::new(args, sender.clone())?;
+    let listener = TcpListener::bind(&args.addr)?;
+    log::info!(
+        "Listening on {}, waiting for {} players",
+        listener.local_addr()?,
+        server.clients.len(),
+    );
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if sender.send(Incoming::Connected(stream)).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => log::warn!("Failed to accept a connection: {e}"),
+            }
+        }
+    });
+
+    let tick_delta = 1.0 / server.config.ticks_per_second;
+    let tick_duration = std::time::Duration::from_secs_f64(tick_delta / server.config.time_scale);
+    let mut next_tick = std::time::Instant::now();
+    loop {
+        for incoming in incoming.try_iter() {
+            match incoming {
+                Incoming::Connected(stream) => server.connect(stream),
+                Incoming::Message(player, id, message) => {
+                    if server.is_connected(player, id) {
+                        server.handle_message(player, message);
+                    }
+                }
+                Incoming::Disconnected(player, id) => {
+                    if server.is_connected(player, id) {
+                        server.disconnect(player);
+                    }
+                }
+            }
+        }
+        // The game only goes on with everyone in
+        if server.missing_players() == 0 {
+            server.tick(tick_delta);
+        }
+        next_tick += tick_duration;
+        let now = std::time::Instant::now();
+        if next_tick > now {
+            std::thread::sleep(next_tick - now);
+        } else {
+            // Do not try to catch up after falling behind
+            next_tick = now;
+        }
+    }
+}
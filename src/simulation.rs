@@ -0,0 +1,987 @@
+use super::*;
+
+use std::collections::{BTreeSet, VecDeque};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SnakeSpeedModifier {
+    time_left: f64,
+    multiplier: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Results {
+    pub time: f64,
+    pub win: bool,
+    pub score: usize,
+}
+
+/// What a local player controls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Runner,
+    /// Steers a snake chasing the runners instead of its AI
+    Snake,
+}
+
+/// State of one of the local players
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Player {
+    pub role: Role,
+    /// `None` until spawned on the first tick, the steered snake for [Role::Snake]
+    pub id: Option<Id>,
+    pub held_item: Option<Item>,
+    /// Items this player used on snakes
+    pub score: usize,
+    pub caught: bool,
+    next_move: f64,
+    /// Pressed directions waiting for the player's next steps
+    move_queue: VecDeque<vec2<isize>>,
+    /// Direction of the last step, repeated while it is held
+    last_move_dir: Option<vec2<isize>>,
+    /// Directions held on the last tick, steered snakes step on their own schedule
+    held_dirs: Vec<vec2<isize>>,
+    /// Cell the runner left with their last step, where [Item::Wall] goes
+    trail: Option<vec2<usize>>,
+}
+
+impl Player {
+    /// Next direction to step in: queued presses first, then held directions
+    fn next_move_dir(&mut self, held_dirs: &[vec2<isize>]) -> Option<vec2<isize>> {
+        if let Some(dir) = self.move_queue.pop_front() {
+            return Some(dir);
+        }
+        match self.last_move_dir {
+            Some(dir) if held_dirs.contains(&dir) => Some(dir),
+            _ => held_dirs.first().copied(),
+        }
+    }
+
+    /// Next direction of a steered snake, which keeps going straight unless turned.
+    ///
+    /// Directions `allowed` returns false for, like into the snake's own neck, are skipped.
+    fn next_snake_dir(&mut self, allowed: impl Fn(vec2<isize>) -> bool) -> Option<vec2<isize>> {
+        let queued = std::iter::from_fn(|| self.move_queue.pop_front()).find(|&dir| allowed(dir));
+        let dir = queued
+            .or_else(|| self.held_dirs.iter().copied().find(|&dir| allowed(dir)))
+            .or(self.last_move_dir.filter(|&dir| allowed(dir)));
+        if dir.is_some() {
+            self.last_move_dir = dir;
+        }
+        dir
+    }
+}
+
+/// Input of a single player for a single tick
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    /// Directions pressed since last tick, in order
+    pub moves: Vec<vec2<isize>>,
+    /// Directions currently held down
+    pub held_dirs: Vec<vec2<isize>>,
+    pub use_item: bool,
+}
+
+/// Things that happened during a tick, for presentation purposes
+#[derive(Serialize, Deserialize)]
+pub enum Event {
+    /// Player made the first move, the run has started
+    Started,
+    ItemPickedUp(Item),
+    /// Item was applied to a snake, either eaten by it or used by the player
+    ItemUsed {
+        pos: vec2<usize>,
+        item: Item,
+    },
+    /// Snake under a speed modifier made a step
+    SnakeSped {
+        pos: vec2<usize>,
+        multiplier: f64,
+    },
+    SnakeDied {
+        positions: Vec<vec2<usize>>,
+    },
+    /// One of the players got eaten
+    PlayerCaught {
+        player: usize,
+    },
+    /// Every runner got eaten
+    Lost,
+    /// Map goal was reached, or the runners escaped the steered snakes
+    Won,
+}
+
+/// Everything needed to resume a simulation, see [Simulation::snapshot]
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    seed: u64,
+    /// Seed the random generator was reset to when taking the snapshot
+    rng_seed: u64,
+    id_gen: IdGen,
+    ticks: u64,
+    time: f64,
+    cells: Vec<(vec2<usize>, MapCell)>,
+    #[serde(default)]
+    timers: Vec<(vec2<usize>, f64)>,
+    brains: Vec<(Id, BrainState)>,
+    brains_assigned: usize,
+    initial_dir: Vec<(Id, vec2<isize>)>,
+    players: Vec<Player>,
+    next_snake_move: Vec<(Id, f64)>,
+    next_item: f64,
+    snake_grow: Vec<(Id, usize)>,
+    snake_reversing: Vec<(Id, u32)>,
+    snake_speed_modifier: Vec<(Id, SnakeSpeedModifier)>,
+    #[serde(default)]
+    snake_frozen: Vec<(Id, f64)>,
+    results: Option<Results>,
+    player_moved: bool,
+    score: usize,
+}
+
+impl Snapshot {
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+}
+
+/// Game rules, independent of rendering and audio
+pub struct Simulation {
+    config: Rc<Config>,
+    pub seed: u64,
+    rng: StdRng,
+    id_gen: IdGen,
+    /// Number of ticks simulated so far
+    pub ticks: u64,
+    pub time: f64,
+    pub map: Map,
+    brains: HashMap<Id, Box<dyn SnakeBrain>>,
+    /// Number of brains handed out, cycles through [Config::snake_brains]
+    brains_assigned: usize,
+    /// Direction of the first move of snakes placed by the map
+    initial_dir: HashMap<Id, vec2<isize>>,
+    pub players: Vec<Player>,
+    next_snake_move: HashMap<Id, f64>,
+    next_item: f64,
+    snake_grow: HashMap<Id, usize>,
+    snake_reversing: HashMap<Id, u32>,
+    snake_speed_modifier: HashMap<Id, SnakeSpeedModifier>,
+    /// Seconds left until each frozen snake moves again
+    pub snake_frozen: HashMap<Id, f64>,
+    pub results: Option<Results>,
+    pub player_moved: bool,
+    /// Items used on snakes by anyone, shared by all players
+    pub score: usize,
+}
+
+impl Simulation {
+    pub fn new(config: Rc<Config>, map: Map, seed: u64, roles: &[Role]) -> Self {
+        let mut sim = Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            id_gen: IdGen::new(),
+            map,
+            brains: HashMap::new(),
+            brains_assigned: 0,
+            initial_dir: HashMap::new(),
+            next_snake_move: HashMap::new(),
+            next_item: 0.0,
+            players: roles
+                .iter()
+                .map(|&role| Player { role, ..default() })
+                .collect(),
+            snake_speed_modifier: default(),
+            snake_frozen: default(),
+            results: None,
+            snake_grow: HashMap::new(),
+            snake_reversing: default(),
+            ticks: 0,
+            time: 0.0,
+            player_moved: false,
+            score: 0,
+            config,
+        };
+        let spawns = sim.map.snake_spawns.clone();
+        if spawns.is_empty() {
+            let pos = sim.random_empty_cell();
+            sim.spawn_snake(&[pos]);
+        }
+        for spawn in spawns {
+            let id = sim.spawn_snake(&spawn.body);
+            sim.initial_dir.insert(id, spawn.dir);
+        }
+        // Steered snakes take over the map's snakes in order, extra ones spawn anywhere
+        for index in 0..sim.players.len() {
+            if sim.players[index].role != Role::Snake {
+                continue;
+            }
+            let free_snake = sim
+                .snake_ids()
+                .into_iter()
+                .find(|&id| sim.player_index(id).is_none());
+            let id = match free_snake {
+                Some(id) => id,
+                None => {
+                    let pos = sim.random_empty_cell();
+                    sim.spawn_snake(&[pos])
+                }
+            };
+            sim.players[index].last_move_dir = sim.initial_dir.remove(&id);
+            sim.players[index].id = Some(id);
+        }
+        sim
+    }
+
+    /// Resume from a snapshot of a simulation of `map`
+    pub fn restore(config: Rc<Config>, mut map: Map, snapshot: Snapshot) -> Self {
+        for (pos, cell) in snapshot.cells {
+            map.set(pos, cell);
+        }
+        for (pos, time_left) in snapshot.timers {
+            let cell = map[pos].clone();
+            map.set_timed(pos, cell, time_left);
+        }
+        Self {
+            config,
+            seed: snapshot.seed,
+            rng: StdRng::seed_from_u64(snapshot.rng_seed),
+            id_gen: snapshot.id_gen,
+            ticks: snapshot.ticks,
+            time: snapshot.time,
+            map,
+            brains: snapshot
+                .brains
+                .into_iter()
+                .map(|(id, state)| (id, state.into_brain()))
+                .collect(),
+            brains_assigned: snapshot.brains_assigned,
+            initial_dir: snapshot.initial_dir.into_iter().collect(),
+            players: snapshot.players,
+            next_snake_move: snapshot.next_snake_move.into_iter().collect(),
+            next_item: snapshot.next_item,
+            snake_grow: snapshot.snake_grow.into_iter().collect(),
+            snake_reversing: snapshot.snake_reversing.into_iter().collect(),
+            snake_speed_modifier: snapshot.snake_speed_modifier.into_iter().collect(),
+            snake_frozen: snapshot.snake_frozen.into_iter().collect(),
+            results: snapshot.results,
+            player_moved: snapshot.player_moved,
+            score: snapshot.score,
+        }
+    }
+
+    /// Everything needed to resume this simulation later.
+    ///
+    /// The random generator itself can not be saved,
+    /// so it is reset to a seed drawn from it that goes into the snapshot.
+    pub fn snapshot(&mut self) -> Snapshot {
+        // Sorted so that saving the same state gives the same file
+        fn pairs<T: Clone>(map: &HashMap<Id, T>) -> Vec<(Id, T)> {
+            let mut pairs: Vec<(Id, T)> =
+                map.iter().map(|(&id, value)| (id, value.clone())).collect();
+            pairs.sort_by_key(|&(id, _)| id);
+            pairs
+        }
+        let rng_seed = self.rng.gen();
+        self.reseed(rng_seed);
+        let mut brains: Vec<(Id, BrainState)> = self
+            .brains
+            .iter()
+            .map(|(&id, brain)| (id, brain.state()))
+            .collect();
+        brains.sort_by_key(|&(id, _)| id);
+        Snapshot {
+            seed: self.seed,
+            rng_seed,
+            id_gen: self.id_gen.clone(),
+            ticks: self.ticks,
+            time: self.time,
+            cells: self.map.cells(),
+            timers: self.map.timers(),
+            brains,
+            brains_assigned: self.brains_assigned,
+            initial_dir: pairs(&self.initial_dir),
+            players: self.players.clone(),
+            next_snake_move: pairs(&self.next_snake_move),
+            next_item: self.next_item,
+            snake_grow: pairs(&self.snake_grow),
+            snake_reversing: pairs(&self.snake_reversing),
+            snake_speed_modifier: pairs(&self.snake_speed_modifier),
+            snake_frozen: pairs(&self.snake_frozen),
+            results: self.results.clone(),
+            player_moved: self.player_moved,
+            score: self.score,
+        }
+    }
+
+    /// Reset the random generator, used by snapshots
+    pub fn reseed(&mut self, rng_seed: u64) {
+        self.rng = StdRng::seed_from_u64(rng_seed);
+    }
+
+    fn random_empty_cell(&mut self) -> vec2<usize> {
+        self.map
+            .iter()
+            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
+            .choose(&mut self.rng)
+            .unwrap()
+            .0
+    }
+
+    /// Place a new snake with given body, from tail to head
+    fn spawn_snake(&mut self, body: &[vec2<usize>]) -> Id {
+        let snake_id = self.id_gen.gen();
+        for (segment_index, &pos) in body.iter().enumerate() {
+            self.map.set(
+                pos,
+                MapCell::SnakePart {
+                    snake_id,
+                    segment_index: segment_index as u32,
+                },
+            );
+        }
+        self.next_snake_move
+            .insert(snake_id, self.config.snake_wake_up_time);
+        self.snake_grow.insert(
+            snake_id,
+            self.config.start_snake_size.saturating_sub(body.len()),
+        );
+        let kinds = &self.config.snake_brains;
+        let kind = kinds
+            .get(self.brains_assigned % kinds.len().max(1))
+            .copied()
+            .unwrap_or(BrainKind::Classic);
+        self.brains_assigned += 1;
+        self.brains.insert(snake_id, kind.create(&self.config));
+        snake_id
+    }
+
+    pub fn spawn_player(&mut self) -> Id {
+        let id = self.id_gen.gen();
+        let free_spawn = self
+            .map
+            .player_spawns
+            .iter()
+            .copied()
+            .filter(|&pos| matches!(self.map[pos], MapCell::Empty))
+            .choose(&mut self.rng);
+        let pos = match free_spawn {
+            Some(pos) => pos,
+            None => self.random_empty_cell(),
+        };
+        self.map.set(pos, MapCell::Player(id));
+        id
+    }
+
+    pub fn spawn_item(&mut self) {
+        let num_items = self
+            .map
+            .iter()
+            .filter(|(_, cell)| matches!(cell, MapCell::Item(_)))
+            .count();
+        if num_items >= self.config.max_items {
+            return;
+        }
+        let Some((pos, _)) = self
+            .map
+            .iter()
+            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
+            .choose(&mut self.rng)
+        else {
+            return;
+        };
+        let weights = &self.config.weights;
+        self.map.set(
+            pos,
+            MapCell::Item(
+                [
+                    (weights.food, Item::Food),
+                    (weights.reverse, Item::Reverse),
+                    (weights.snake_speed_up, Item::SnakeSpeedUp),
+                    (weights.snake_speed_down, Item::SnakeSpeedDown),
+                    (weights.snake_split, Item::SnakeSplit),
+                    (weights.freeze, Item::Freeze),
+                    (weights.wall, Item::Wall),
+                    (weights.decoy, Item::Decoy),
+                    (weights.dash, Item::Dash),
+                    (weights.teleport, Item::Teleport),
+                ]
+                .choose_weighted(&mut self.rng, |&(weight, _)| weight)
+                .unwrap()
+                .1
+                .clone(),
+            ),
+        );
+    }
+
+    pub fn player_pos(&self, player: usize) -> Option<vec2<usize>> {
+        self.players[player]
+            .id
+            .and_then(|id| self.map.player_pos(id))
+    }
+
+    /// Whether some player steers a snake, making this a versus match
+    pub fn is_versus(&self) -> bool {
+        self.players.iter().any(|player| player.role == Role::Snake)
+    }
+
+    /// Index of the player with given id, runner or steered snake
+    pub fn player_index(&self, id: Id) -> Option<usize> {
+        self.players.iter().position(|player| player.id == Some(id))
+    }
+
+    fn move_player(&mut self, player: usize, dir: vec2<isize>, events: &mut Vec<Event>) {
+        self.players[player].last_move_dir = Some(dir);
+        if !self.player_moved {
+            self.player_moved = true;
+            events.push(Event::Started);
+        }
+        self.players[player].next_move = 1.0 / self.config.player_speed;
+        if let Some(pos) = self.player_pos(player) {
+            let new_pos = self.map.add_dir(pos, dir);
+            match &self.map[new_pos] {
+                MapCell::Empty => {}
+                MapCell::Item(item) => {
+                    if matches!(item, Item::Food) {
+                        return;
+                    }
+                    if self.players[player].held_item.is_some() {
+                        return;
+                    }
+                    events.push(Event::ItemPickedUp(item.clone()));
+                    self.players[player].held_item = Some(item.clone());
+                }
+                _ => return,
+            }
+            let cell = self.map.set(pos, MapCell::Empty);
+            self.map.set(new_pos, cell);
+            self.players[player].trail = Some(pos);
+        }
+    }
+
+    /// Ids of all alive snakes, in a stable order
+    pub fn snake_ids(&self) -> BTreeSet<Id> {
+        self.map.snake_ids().collect()
+    }
+
+    fn use_held_item(&mut self, player: usize, events: &mut Vec<Event>) {
+        let Some(player_pos) = self.player_pos(player) else {
+            return;
+        };
+        let Some(item) = self.players[player].held_item.take() else {
+            return;
+        };
+        // Some items are used by the player themselves, the rest go to the closest snake
+        let used_at = match item {
+            Item::Wall => self.place_behind(player, MapCell::Wall, self.config.items.wall.time),
+            Item::Decoy => self.place_behind(player, MapCell::Decoy, self.config.items.decoy.time),
+            Item::Dash => self.dash(player),
+            Item::Teleport => self.teleport(player),
+            _ => {
+                let snake_id = self
+                    .snake_ids()
+                    .into_iter()
+                    .min_by_key(|&id| self.map.distance(snake::head(id, &self.map), player_pos));
+                if let Some(snake_id) = snake_id {
+                    events.push(Event::ItemUsed {
+                        pos: snake::head(snake_id, &self.map),
+                        item: item.clone(),
+                    });
+                    self.players[player].score += 1;
+                    self.use_item(snake_id, item);
+                }
+                return;
+            }
+        };
+        match used_at {
+            Some(pos) => events.push(Event::ItemUsed { pos, item }),
+            // Nowhere to use it right now, so it is kept for later
+            None => self.players[player].held_item = Some(item),
+        }
+    }
+
+    /// Put `cell` for `time` seconds on the cell the player just left,
+    /// or next to them if that one is taken
+    fn place_behind(&mut self, player: usize, cell: MapCell, time: f64) -> Option<vec2<usize>> {
+        let player_pos = self.player_pos(player)?;
+        let pos = self.players[player]
+            .trail
+            .into_iter()
+            .chain(self.map.neighbors(player_pos))
+            .find(|&pos| self.map[pos] == MapCell::Empty)?;
+        self.map.set_timed(pos, cell, time);
+        Some(pos)
+    }
+
+    /// Move the player several cells in their last direction at once,
+    /// stopping in front of anything in the way. Returns where they ended up
+    fn dash(&mut self, player: usize) -> Option<vec2<usize>> {
+        let start = self.player_pos(player)?;
+        let dir = self.players[player].last_move_dir?;
+        let mut pos = start;
+        for _ in 0..self.config.items.dash.cells {
+            let next = self.map.add_dir(pos, dir);
+            if self.map[next] != MapCell::Empty {
+                break;
+            }
+            self.players[player].trail = Some(pos);
+            pos = next;
+        }
+        if pos == start {
+            return None;
+        }
+        let cell = self.map.set(start, MapCell::Empty);
+        self.map.set(pos, cell);
+        Some(pos)
+    }
+
+    /// Move the player to a random empty cell far enough from every snake head,
+    /// or to the farthest one if none is. Returns where they ended up
+    fn teleport(&mut self, player: usize) -> Option<vec2<usize>> {
+        let start = self.player_pos(player)?;
+        let heads: Vec<vec2<usize>> = self
+            .snake_ids()
+            .into_iter()
+            .map(|id| snake::head(id, &self.map))
+            .collect();
+        let cells: Vec<(vec2<usize>, usize)> = self
+            .map
+            .iter()
+            .filter(|(_, cell)| matches!(cell, MapCell::Empty))
+            .map(|(pos, _)| {
+                let safety = heads
+                    .iter()
+                    .map(|&head| self.map.distance(head, pos))
+                    .min()
+                    .unwrap_or(usize::MAX);
+                (pos, safety)
+            })
+            .collect();
+        let farthest = cells.iter().map(|&(_, safety)| safety).max()?;
+        let min_distance = self.config.items.teleport.min_distance.min(farthest);
+        let &(pos, _) = cells
+            .iter()
+            .filter(|&&(_, safety)| safety >= min_distance)
+            .choose(&mut self.rng)
+            .unwrap();
+        let cell = self.map.set(start, MapCell::Empty);
+        self.map.set(pos, cell);
+        self.players[player].trail = None;
+        Some(pos)
+    }
+
+    fn use_item(&mut self, id: Id, item: Item) {
+        self.score += 1;
+        match item {
+            Item::Food => {
+                *self.snake_grow.entry(id).or_default() += self.config.food_value - 1;
+            }
+            Item::Reverse => {
+                let head_idx = match self.map[snake::head(id, &self.map)] {
+                    MapCell::SnakePart {
+                        snake_id,
+                        segment_index,
+                    } if snake_id == id => segment_index,
+                    _ => unreachable!(),
+                };
+                if head_idx > 0 {
+                    self.snake_reversing.insert(id, head_idx - 1);
+                }
+                // Steered snakes wait for a new direction after turning around
+                if let Some(player) = self.player_index(id) {
+                    self.players[player].last_move_dir = None;
+                }
+                // Old plans make no sense with the head on the other end
+                let kind = self.brains[&id].kind();
+                self.brains.insert(id, kind.create(&self.config));
+            }
+            Item::SnakeSpeedUp => {
+                self.snake_speed_modifier.insert(
+                    id,
+                    SnakeSpeedModifier {
+                        time_left: self.config.items.snake_speed.time,
+                        multiplier: self.config.items.snake_speed.multiplier,
+                    },
+                );
+            }
+            Item::SnakeSpeedDown => {
+                self.snake_speed_modifier.insert(
+                    id,
+                    SnakeSpeedModifier {
+                        time_left: self.config.items.snake_speed.time,
+                        multiplier: 1.0 / self.config.items.snake_speed.multiplier,
+                    },
+                );
+            }
+            Item::SnakeSplit => {
+                let body: Vec<vec2<usize>> = self.map.snake_body(id).collect();
+                // A single segment has no half to split off
+                if body.len() < 2 {
+                    return;
+                }
+                let new_id = self.id_gen.gen();
+                self.next_snake_move
+                    .insert(new_id, self.config.snake_wake_up_time);
+                let kind = self.brains[&id].kind();
+                self.brains.insert(new_id, kind.create(&self.config));
+                for &pos in &body[..body.len() / 2] {
+                    let MapCell::SnakePart { segment_index, .. } = self.map[pos] else {
+                        unreachable!()
+                    };
+                    self.map.set(
+                        pos,
+                        MapCell::SnakePart {
+                            snake_id: new_id,
+                            segment_index,
+                        },
+                    );
+                }
+            }
+            Item::Freeze => {
+                self.snake_frozen.insert(id, self.config.items.freeze.time);
+            }
+            // Used by the player instead, see [Self::use_held_item]
+            Item::Wall | Item::Decoy | Item::Dash | Item::Teleport => {}
+        }
+    }
+
+    fn results(&self, win: bool) -> Results {
+        Results {
+            time: self.time,
+            win,
+            score: self.score,
+        }
+    }
+
+    fn move_snake(&mut self, id: Id, events: &mut Vec<Event>) {
+        let next_move = self.next_snake_move.get_mut(&id).unwrap();
+        if let Some(next_eat_index) = self.snake_reversing.remove(&id) {
+            *next_move = 1.0 / self.config.snake_reverse_speed;
+            let head_pos = snake::head(id, &self.map);
+            let head_index = match self.map[head_pos] {
+                MapCell::SnakePart {
+                    snake_id,
+                    segment_index,
+                } if snake_id == id => segment_index,
+                _ => unreachable!(),
+            };
+            let next = self
+                .map
+                .neighbors(head_pos)
+                .find(|&next| match self.map[next] {
+                    MapCell::SnakePart {
+                        snake_id,
+                        segment_index,
+                    } => snake_id == id && segment_index == next_eat_index,
+                    _ => false,
+                });
+            if let Some(next) = next {
+                self.map.set(
+                    next,
+                    MapCell::SnakePart {
+                        snake_id: id,
+                        segment_index: head_index + 1,
+                    },
+                );
+                if next_eat_index > 0 {
+                    self.snake_reversing.insert(id, next_eat_index - 1);
+                }
+            }
+            return;
+        }
+
+        *next_move = 1.0
+            / self.config.snake_speed
+            / self
+                .snake_speed_modifier
+                .get(&id)
+                .map_or(1.0, |modifier| modifier.multiplier);
+
+        if let Some(modifier) = self.snake_speed_modifier.get(&id) {
+            events.push(Event::SnakeSped {
+                pos: snake::head(id, &self.map),
+                multiplier: modifier.multiplier,
+            });
+        }
+
+        let head_pos = snake::head(id, &self.map);
+        let dir = if let Some(player) = self.player_index(id) {
+            let body: Vec<vec2<usize>> = self.map.snake_body(id).collect();
+            let neck = body.len().checked_sub(2).map(|index| body[index]);
+            let map = &self.map;
+            let dir =
+                self.players[player].next_snake_dir(|dir| Some(map.add_dir(head_pos, dir)) != neck);
+            if dir.is_none() {
+                // Not steered anywhere yet
+                return;
+            }
+            dir
+        } else {
+            match self.initial_dir.remove(&id) {
+                Some(dir) if snake::can_enter(id, &self.map, self.map.add_dir(head_pos, dir)) => {
+                    Some(dir)
+                }
+                _ => self
+                    .brains
+                    .get_mut(&id)
+                    .unwrap()
+                    .think(id, &self.map, &mut self.rng),
+            }
+        };
+        let snake_grow = self.snake_grow.entry(id).or_default();
+        let moved = match dir {
+            Some(dir) => snake::advance(id, &mut self.map, dir, *snake_grow == 0),
+            None => Err(()),
+        };
+        match moved {
+            Ok(Some(item)) => {
+                events.push(Event::ItemUsed {
+                    pos: snake::head(id, &self.map),
+                    item: item.clone(),
+                });
+                self.use_item(id, item);
+                self.spawn_item();
+            }
+            Ok(None) => {}
+            Err(()) => {
+                let positions: Vec<vec2<usize>> = self.map.snake_body(id).collect();
+                for &pos in &positions {
+                    self.map.set(pos, MapCell::Empty);
+                }
+                self.brains.remove(&id);
+                events.push(Event::SnakeDied { positions });
+            }
+        }
+        let snake_grow = self.snake_grow.entry(id).or_default();
+        if *snake_grow > 0 {
+            *snake_grow -= 1;
+        }
+    }
+
+    /// Whether the runners of a versus match outlasted the steered snakes,
+    /// by surviving the round time or by all of them dying
+    fn runners_escaped(&self) -> bool {
+        if !self.is_versus() {
+            return false;
+        }
+        self.time >= self.config.versus.round_time
+            || self
+                .players
+                .iter()
+                .filter(|player| player.role == Role::Snake)
+                .all(|player| {
+                    player
+                        .id
+                        .is_none_or(|id| self.map.snake_body(id).next().is_none())
+                })
+    }
+
+    /// Advance the game by `delta_time` seconds, with one input per player
+    pub fn tick(&mut self, delta_time: f64, inputs: &[Input]) -> Vec<Event> {
+        let mut events = Vec::new();
+        let no_input = Input::default();
+        for index in 0..self.players.len() {
+            let input = inputs.get(index).unwrap_or(&no_input);
+            if input.use_item {
+                self.use_held_item(index, &mut events);
+            }
+            let player = &mut self.players[index];
+            for &dir in &input.moves {
+                if player.move_queue.len() < self.config.move_queue_size {
+                    player.move_queue.push_back(dir);
+                }
+            }
+            match player.role {
+                Role::Runner => {
+                    if player.next_move <= 0.0 {
+                        if let Some(dir) = player.next_move_dir(&input.held_dirs) {
+                            self.move_player(index, dir, &mut events);
+                        }
+                    }
+                }
+                // Steered snakes step on the snake schedule, see [Self::move_snake]
+                Role::Snake => player.held_dirs = input.held_dirs.clone(),
+            }
+        }
+
+        if self.player_moved {
+            self.time += delta_time;
+        }
+        if self.results.is_none() {
+            for index in 0..self.players.len() {
+                if self.players[index].role != Role::Runner {
+                    continue;
+                }
+                if self.players[index].id.is_none() {
+                    self.players[index].id = Some(self.spawn_player());
+                } else if !self.players[index].caught && self.player_pos(index).is_none() {
+                    self.players[index].caught = true;
+                    events.push(Event::PlayerCaught { player: index });
+                }
+            }
+            let all_caught = self
+                .players
+                .iter()
+                .filter(|player| player.role == Role::Runner)
+                .all(|player| player.caught);
+            if all_caught {
+                self.results = Some(self.results(false));
+                events.push(Event::Lost);
+            } else if self.runners_escaped() {
+                self.results = Some(self.results(true));
+                events.push(Event::Won);
+            } else if let Some(goal) = &self.map.header.goal {
+                if goal.is_reached(self.time, self.score) {
+                    self.results = Some(self.results(true));
+                    events.push(Event::Won);
+                }
+            }
+        }
+
+        if self.player_moved {
+            for id in self.snake_ids() {
+                if let Some(modifier) = self.snake_speed_modifier.get_mut(&id) {
+                    modifier.time_left -= delta_time;
+                    if modifier.time_left < 0.0 {
+                        self.snake_speed_modifier.remove(&id);
+                    }
+                }
+                if let Some(time_left) = self.snake_frozen.get_mut(&id) {
+                    *time_left -= delta_time;
+                    if *time_left < 0.0 {
+                        self.snake_frozen.remove(&id);
+                    }
+                    continue;
+                }
+                let next_move = self.next_snake_move.entry(id).or_default();
+                *next_move -= delta_time;
+                if *next_move < 0.0 {
+                    self.move_snake(id, &mut events);
+                }
+            }
+
+            self.map.update_timers(delta_time);
+
+            self.next_item -= delta_time;
+            if self.next_item < 0.0 {
+                self.next_item = self.config.new_item_time;
+                self.spawn_item();
+            }
+        }
+
+        for player in &mut self.players {
+            player.next_move -= delta_time;
+        }
+
+        self.ticks += 1;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Rc<Config> {
+        Rc::new(toml::from_str(include_str!("../assets/config.toml")).unwrap())
+    }
+
+    fn simulation(map: &str, seed: u64) -> Simulation {
+        Simulation::new(config(), Map::parse(map).unwrap(), seed, &[Role::Runner])
+    }
+
+    /// Tick until the game is over or `ticks` run out, returning every event
+    fn run(sim: &mut Simulation, ticks: usize, input: impl Fn(usize) -> Input) -> Vec<Event> {
+        let delta_time = 1.0 / sim.config.ticks_per_second;
+        let mut events = Vec::new();
+        for tick in 0..ticks {
+            events.extend(sim.tick(delta_time, &[input(tick)]));
+            if sim.results.is_some() {
+                break;
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn snake_eats_player() {
+        let mut sim = simulation(
+            "topology = \"bounded\"\n---\n\
+             #########\n\
+             #@<o    #\n\
+             #########",
+            0,
+        );
+        // Bumping into the wall starts the game without moving anywhere
+        let events = run(&mut sim, 300, |tick| Input {
+            moves: if tick == 0 { vec![vec2(0, 1)] } else { vec![] },
+            ..default()
+        });
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::PlayerCaught { player: 0 })));
+        assert!(events.iter().any(|event| matches!(event, Event::Lost)));
+        assert!(!sim.results.as_ref().unwrap().win);
+    }
+
+    #[test]
+    fn player_picks_up_item() {
+        let mut sim = simulation(
+            "topology = \"bounded\"\n---\n\
+             #########\n\
+             #@r     #\n\
+             #########\n\
+             #o>     #\n\
+             #########",
+            0,
+        );
+        let events = run(&mut sim, 30, |_| Input {
+            held_dirs: vec![vec2(1, 0)],
+            ..default()
+        });
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::ItemPickedUp(Item::Reverse))));
+        assert!(sim.players[0].held_item == Some(Item::Reverse));
+        assert!(sim.results.is_none());
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = |seed: u64| {
+            let mut sim = simulation(include_str!("../assets/map.txt"), seed);
+            run(&mut sim, 600, |tick| Input {
+                held_dirs: vec![DIRECTIONS[tick / 40 % DIRECTIONS.len()]],
+                use_item: tick % 50 == 0,
+                ..default()
+            });
+            serde_json::to_string(&sim.snapshot()).unwrap()
+        };
+        assert_eq!(play(42), play(42));
+    }
+
+    #[test]
+    fn restored_game_goes_on_the_same() {
+        let map = include_str!("../assets/map.txt");
+        let input = |tick: usize| Input {
+            moves: vec![DIRECTIONS[tick / 7 % DIRECTIONS.len()]],
+            held_dirs: vec![DIRECTIONS[tick / 40 % DIRECTIONS.len()]],
+            use_item: tick % 50 == 0,
+        };
+        let mut sim = simulation(map, 42);
+        run(&mut sim, 300, input);
+        // Through JSON, the same as a save file
+        let snapshot = serde_json::to_string(&sim.snapshot()).unwrap();
+        let mut restored = Simulation::restore(
+            config(),
+            Map::parse(map).unwrap(),
+            serde_json::from_str(&snapshot).unwrap(),
+        );
+        run(&mut sim, 300, |tick| input(300 + tick));
+        run(&mut restored, 300, |tick| input(300 + tick));
+        assert_eq!(
+            serde_json::to_string(&sim.snapshot()).unwrap(),
+            serde_json::to_string(&restored.snapshot()).unwrap(),
+        );
+    }
+}
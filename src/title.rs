@@ -0,0 +1,83 @@
+use super::*;
+
+use menu::{Menu, MenuInput};
+
+const PLAY: usize = 0;
+const COOP: usize = 1;
+const VERSUS: usize = 2;
+const OPTIONS: usize = 3;
+
+/// First screen shown on startup
+pub struct Title {
+    ctx: Context,
+    menu: Menu,
+    transition: Option<geng::state::Transition>,
+}
+
+impl Title {
+    pub fn new(ctx: &Context) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            menu: Menu::new(
+                "Snaked",
+                vec![
+                    "Play".to_owned(),
+                    "Co-op".to_owned(),
+                    "Versus".to_owned(),
+                    "Options".to_owned(),
+                ],
+            ),
+            transition: None,
+        }
+    }
+}
+
+impl geng::State for Title {
+    fn transition(&mut self) -> Option<geng::state::Transition> {
+        self.transition.take()
+    }
+    fn update(&mut self, _delta_time: f64) {
+        for event in self.ctx.gamepad_events() {
+            self.handle_event(event);
+        }
+    }
+    fn handle_event(&mut self, event: geng::Event) {
+        let controls = self.ctx.controls.borrow().clone();
+        if let geng::Event::KeyPress { key } = event {
+            if controls.fullscreen.contains(&key) {
+                self.ctx.geng.window().toggle_fullscreen();
+            }
+        }
+        let Some(input) = MenuInput::from_event(&controls, &event) else {
+            return;
+        };
+        if self.menu.navigate(input) != Some(MenuInput::Select) {
+            return;
+        }
+        self.transition = match self.menu.selected {
+            PLAY => Some(geng::state::Transition::Switch(Box::new(LevelSelect::new(
+                &self.ctx,
+                &[Role::Runner],
+            )))),
+            COOP => Some(geng::state::Transition::Switch(Box::new(LevelSelect::new(
+                &self.ctx,
+                &[Role::Runner, Role::Runner],
+            )))),
+            VERSUS => Some(geng::state::Transition::Switch(Box::new(LevelSelect::new(
+                &self.ctx,
+                &[Role::Runner, Role::Snake],
+            )))),
+            OPTIONS => Some(geng::state::Transition::Push(Box::new(
+                options::OptionsMenu::new(&self.ctx),
+            ))),
+            _ => unreachable!(),
+        };
+    }
+    fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
+        let hint = format!(
+            "Snake but you are food - Up/Down to select, {} to confirm",
+            Controls::key_name(&self.ctx.controls.borrow().use_item)
+        );
+        self.menu.draw(&self.ctx, framebuffer, &hint);
+    }
+}